@@ -0,0 +1,99 @@
+use crate::order::Quantity;
+
+/// How the lots left over after flooring proportional shares are handed out.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Rounding {
+    // one lot each to the orders with the largest fractional share, earliest first on ties
+    #[default]
+    FloorThenLargestRemainder,
+    // one lot each to the earliest orders
+    FloorThenTimePriority,
+}
+
+/// Splits `quantity` across orders in proportion to their `sizes`, given in time priority order.
+/// Every allocation is at most the order's size and the allocations always sum to
+/// `min(quantity, sizes.sum())`.
+pub fn pro_rata(quantity: Quantity, sizes: &[Quantity], rounding: Rounding) -> Vec<Quantity> {
+    let total: u64 = sizes.iter().map(|size| *size as u64).sum();
+    if total == 0 {
+        return vec![0; sizes.len()];
+    }
+    let quantity = (quantity as u64).min(total);
+
+    // floored shares, along with what flooring dropped from each
+    let mut allocations: Vec<Quantity> = Vec::with_capacity(sizes.len());
+    let mut remainders: Vec<u64> = Vec::with_capacity(sizes.len());
+    for size in sizes {
+        let share = quantity * *size as u64;
+        allocations.push((share / total) as Quantity);
+        remainders.push(share % total);
+    }
+
+    let allocated: u64 = allocations
+        .iter()
+        .map(|allocation| *allocation as u64)
+        .sum();
+    let mut residual = quantity - allocated;
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    if rounding == Rounding::FloorThenLargestRemainder {
+        // stable, so earlier orders win ties
+        order.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]));
+    }
+
+    // flooring drops less than one lot per order, so a single pass always suffices
+    for index in order {
+        if residual == 0 {
+            break;
+        }
+        if allocations[index] < sizes[index] {
+            allocations[index] += 1;
+            residual -= 1;
+        }
+    }
+
+    allocations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pro_rata_conserves_quantity() {
+        for rounding in [
+            Rounding::FloorThenLargestRemainder,
+            Rounding::FloorThenTimePriority,
+        ] {
+            let allocations = pro_rata(100, &[33, 33, 34], rounding);
+            assert_eq!(allocations, vec![33, 33, 34]);
+
+            // floored shares only sum to 97 here
+            let allocations = pro_rata(100, &[30, 30, 30, 11], rounding);
+            assert_eq!(allocations.iter().sum::<Quantity>(), 100);
+
+            let allocations = pro_rata(10, &[33, 33, 34], rounding);
+            assert_eq!(allocations.iter().sum::<Quantity>(), 10);
+        }
+    }
+
+    #[test]
+    fn test_pro_rata_residual_distribution() {
+        // exact shares are 3.3, 3.3 and 3.4
+        assert_eq!(
+            pro_rata(10, &[33, 33, 34], Rounding::FloorThenLargestRemainder),
+            vec![3, 3, 4]
+        );
+        assert_eq!(
+            pro_rata(10, &[33, 33, 34], Rounding::FloorThenTimePriority),
+            vec![4, 3, 3]
+        );
+    }
+
+    #[test]
+    fn test_pro_rata_edges() {
+        assert_eq!(pro_rata(10, &[0, 0], Rounding::default()), vec![0, 0]);
+        assert_eq!(pro_rata(500, &[10, 20], Rounding::default()), vec![10, 20]);
+        assert_eq!(pro_rata(0, &[10, 20], Rounding::default()), vec![0, 0]);
+    }
+}
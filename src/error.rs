@@ -1,4 +1,4 @@
-use crate::order::{OrderId, Quantity, Side};
+use crate::order::{OrderId, OwnerId, Price, Quantity, Side};
 use thiserror::Error;
 
 /// Error enum for OrderBook.
@@ -8,37 +8,119 @@ pub enum OrderBookError {
     OrderNotFound(OrderId),
     #[error("Order {0} already exists in book...")]
     OrderAlreadyExists(OrderId),
+    #[error("Order {0} was already filled...")]
+    OrderAlreadyFilled(OrderId),
+    #[error("Order id {0} was used by a recently filled or cancelled order...")]
+    OrderIdRecentlyUsed(OrderId),
+    #[error(
+        "Order {order_id} quantity {quantity} exceeds the book's maximum of {max_quantity}..."
+    )]
+    QuantityTooLarge {
+        order_id: OrderId,
+        quantity: Quantity,
+        max_quantity: Quantity,
+    },
+    #[error("Order {order_id} at price {price} would cross the book...")]
+    WouldCross { order_id: OrderId, price: Price },
+    #[error("Modify of order {order_id} with sequence {client_seq} arrived after sequence {last_client_seq}...")]
+    StaleModification {
+        order_id: OrderId,
+        client_seq: u64,
+        last_client_seq: u64,
+    },
+    #[error("Order {order_id}'s lock was poisoned by a panicking thread...")]
+    LockPoisoned { order_id: OrderId },
     #[error("Book's side is empty...")]
     BookSideEmpty(Side),
-    #[error("...")]
-    InternalOrderProcessingError(String),
+    #[error("Book tried to overfill order {order_id} by {surplus} qty...")]
+    FillOverflow {
+        order_id: OrderId,
+        surplus: Quantity,
+    },
+    #[error("Book's tracking of order {order_id} doesn't match its price levels...")]
+    TrackingDesync { order_id: OrderId },
+    #[error("Book tried to modify order... {0}")]
+    ModificationError(String),
+    #[error("Invalid price '{0}'...")]
+    InvalidPrice(String),
+    #[error("No book for symbol '{0}'...")]
+    UnknownSymbol(String),
+    #[error("Order {0} found nothing on the other side of the book to trade with...")]
+    NoLiquidity(OrderId),
+    #[error("Owner {owner} exceeded their order entry rate...")]
+    RateLimited { owner: OwnerId },
 }
 
 /// Error enum for an Order.
 #[derive(Error, Debug, PartialEq)]
 pub enum OrderError {
-    #[error("Tried to overfill Order by {surplus} qty...")]
-    RequestedFillTooLarge { surplus: Quantity },
+    #[error("Tried to overfill Order {order_id} by {surplus} qty...")]
+    RequestedFillTooLarge {
+        order_id: OrderId,
+        surplus: Quantity,
+    },
     #[error("")]
     ModificationError(String),
+    #[error("Invalid price '{0}'...")]
+    InvalidPrice(String),
 }
 
 impl From<OrderError> for OrderBookError {
     fn from(value: OrderError) -> Self {
         match value {
-            OrderError::RequestedFillTooLarge { surplus: quantity } => {
-                Self::InternalOrderProcessingError(format!(
-                    "Book tried to overfill an order by {}",
-                    quantity
-                ))
+            OrderError::RequestedFillTooLarge { order_id, surplus } => {
+                Self::FillOverflow { order_id, surplus }
             }
-            OrderError::ModificationError(err_msg) => Self::InternalOrderProcessingError(format!(
-                "Book tried to modify order... {}",
-                err_msg
-            )),
+            OrderError::ModificationError(err_msg) => Self::ModificationError(err_msg),
+            OrderError::InvalidPrice(price) => Self::InvalidPrice(price),
         }
     }
 }
 
+/// Error enum for decoding the binary wire format.
+#[derive(Error, Debug, PartialEq)]
+pub enum WireError {
+    #[error("Buffer holds {actual} bytes, expected {expected}...")]
+    Truncated { expected: usize, actual: usize },
+    #[error("Unsupported wire format version {0}...")]
+    UnsupportedVersion(u8),
+    #[error("Invalid {field} tag {value}...")]
+    InvalidTag { field: &'static str, value: u8 },
+    #[error("Remaining quantity {remaining} plus hidden quantity {hidden} exceeds initial quantity {initial}...")]
+    QuantityExceedsInitial {
+        initial: Quantity,
+        remaining: Quantity,
+        hidden: Quantity,
+    },
+    #[error("Reserve order has a display quantity of 0...")]
+    ZeroDisplayQuantity,
+    #[error("Order without a reserve has hidden quantity {0}...")]
+    HiddenWithoutReserve(Quantity),
+    #[error("Reserve order shows {remaining}, more than its display quantity {display}...")]
+    RemainingExceedsDisplay {
+        remaining: Quantity,
+        display: Quantity,
+    },
+    #[error("Trade's bid quantity {bid} doesn't match its ask quantity {ask}...")]
+    TradeQuantityMismatch { bid: Quantity, ask: Quantity },
+}
+
+/// Error enum for parsing FIX-like order entry messages.
+#[derive(Error, Debug, PartialEq)]
+pub enum FixError {
+    #[error("Field '{0}' is not a tag=value pair...")]
+    MalformedField(String),
+    #[error("Tag {0} is not supported...")]
+    UnknownTag(u32),
+    #[error("Tag {0} appears more than once...")]
+    DuplicateTag(u32),
+    #[error("Required tag {0} is missing...")]
+    MissingTag(u32),
+    #[error("Tag {tag} has invalid value '{value}'...")]
+    InvalidValue { tag: u32, value: String },
+}
+
 pub type BookResult<T> = std::result::Result<T, OrderBookError>;
 pub type OrdResult<T> = std::result::Result<T, OrderError>;
+pub type WireResult<T> = std::result::Result<T, WireError>;
+pub type FixResult<T> = std::result::Result<T, FixError>;
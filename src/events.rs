@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    order::{OrderId, Price, Quantity, Side},
+    orderbook::{LevelInfo, LevelInfos, OrderBookLevelInfos},
+};
+
+/// A change to the orders resting in a book, see [`OrderBook::drain_events`](crate::orderbook::OrderBook::drain_events).
+/// Quantities are visible quantities, so hidden reserve quantity only shows up once refreshed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BookEvent {
+    // order started resting in the book
+    OrderAdded {
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+    // order left the book without trading its remaining quantity
+    OrderRemoved {
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+    // part or all of a resting order traded
+    OrderFilled {
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+    // a reserve order showed another slice of its hidden quantity
+    OrderRefreshed {
+        order_id: OrderId,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    },
+}
+
+/// Rebuilds a book's aggregated levels purely from its [`BookEvent`]s.
+#[derive(Debug, Default)]
+pub struct DepthReconstructor {
+    // visible quantity of each order, by price level
+    bids: BTreeMap<Price, HashMap<OrderId, Quantity>>,
+    asks: BTreeMap<Price, HashMap<OrderId, Quantity>>,
+}
+
+impl DepthReconstructor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies one event to the reconstructed levels.
+    pub fn apply(&mut self, event: &BookEvent) {
+        match *event {
+            BookEvent::OrderAdded {
+                order_id,
+                side,
+                price,
+                quantity,
+            }
+            | BookEvent::OrderRefreshed {
+                order_id,
+                side,
+                price,
+                quantity,
+            } => {
+                *self
+                    .levels_mut(side)
+                    .entry(price)
+                    .or_default()
+                    .entry(order_id)
+                    .or_default() += quantity;
+            }
+            BookEvent::OrderRemoved {
+                order_id,
+                side,
+                price,
+                quantity,
+            }
+            | BookEvent::OrderFilled {
+                order_id,
+                side,
+                price,
+                quantity,
+            } => {
+                let levels = self.levels_mut(side);
+                let Some(orders) = levels.get_mut(&price) else {
+                    return;
+                };
+                if let Some(order_quantity) = orders.get_mut(&order_id) {
+                    *order_quantity = order_quantity.saturating_sub(quantity);
+                    if *order_quantity == 0 {
+                        orders.remove(&order_id);
+                    }
+                }
+                if orders.is_empty() {
+                    levels.remove(&price);
+                }
+            }
+        }
+    }
+
+    /// Reconstructed levels, ordered like [`OrderBook::get_order_infos`](crate::orderbook::OrderBook::get_order_infos).
+    pub fn get_order_infos(&self) -> OrderBookLevelInfos {
+        let level_infos = |levels: &BTreeMap<Price, HashMap<OrderId, Quantity>>| -> LevelInfos {
+            levels
+                .iter()
+                .map(|(price, orders)| LevelInfo::new(*price, orders.values().sum(), orders.len()))
+                .collect()
+        };
+        OrderBookLevelInfos::new(&level_infos(&self.bids), &level_infos(&self.asks))
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Price, HashMap<OrderId, Quantity>> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::BookResult, order::*, orderbook::OrderBook};
+
+    fn gtc(order_id: OrderId, side: Side, price: Price, quantity: Quantity) -> Order {
+        Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity)
+    }
+
+    #[test]
+    fn test_depth_reconstructor_tracks_book() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_event_recording(true);
+        let mut reconstructor = DepthReconstructor::new();
+
+        book.submit(gtc(1, Side::Buy, 100, 10))?;
+        book.submit(gtc(2, Side::Buy, 99, 20))?;
+        book.submit(gtc(3, Side::Sell, 103, 15))?;
+        book.submit(gtc(4, Side::Sell, 102, 5).with_reserve(5))?;
+        book.submit(gtc(5, Side::Sell, 102, 30).with_reserve(10))?;
+        book.submit(gtc(6, Side::Buy, 102, 22))?;
+        book.modify_order(OrderModify::new(2, None, Some(101), None))?;
+        book.cancel_order(1)?;
+        book.submit(gtc(7, Side::Sell, 100, 25))?;
+
+        for event in book.drain_events() {
+            reconstructor.apply(&event);
+        }
+
+        assert_eq!(reconstructor.get_order_infos(), book.get_order_infos());
+        assert!(book.drain_events().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_recording_is_opt_in() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.submit(gtc(1, Side::Buy, 100, 10))?;
+        assert!(book.drain_events().is_empty());
+
+        book.set_event_recording(true);
+        book.submit(gtc(2, Side::Sell, 100, 4))?;
+        assert_eq!(
+            book.drain_events(),
+            vec![
+                BookEvent::OrderAdded {
+                    order_id: 2,
+                    side: Side::Sell,
+                    price: 100,
+                    quantity: 4
+                },
+                BookEvent::OrderFilled {
+                    order_id: 1,
+                    side: Side::Buy,
+                    price: 100,
+                    quantity: 4
+                },
+                BookEvent::OrderFilled {
+                    order_id: 2,
+                    side: Side::Sell,
+                    price: 100,
+                    quantity: 4
+                },
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Minimal parser for FIX-like `tag=value` order entry messages, e.g.
+//! `35=D|54=1|38=100|44=10000|40=2|11=7`. Fields are separated by `|` or SOH.
+//!
+//! Only New Order Single (`35=D`) limit orders (`40=2`) are supported. Supported tags:
+//!
+//! | tag | field | values |
+//! |-----|-------|--------|
+//! | 35 | MsgType | `D` |
+//! | 11 | ClOrdID | order id |
+//! | 54 | Side | `1` buy, `2` sell |
+//! | 38 | OrderQty | quantity |
+//! | 44 | Price | price in cents |
+//! | 40 | OrdType | `2` limit |
+//! | 59 | TimeInForce, optional | `0` day (default), `1` good till cancel, `3` fill and kill |
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{
+    error::{FixError, FixResult},
+    order::{Order, OrderType, Side},
+};
+
+const MSG_TYPE: u32 = 35;
+const CL_ORD_ID: u32 = 11;
+const SIDE: u32 = 54;
+const ORDER_QTY: u32 = 38;
+const PRICE: u32 = 44;
+const ORD_TYPE: u32 = 40;
+const TIME_IN_FORCE: u32 = 59;
+
+const SUPPORTED_TAGS: [u32; 7] = [
+    MSG_TYPE,
+    CL_ORD_ID,
+    SIDE,
+    ORDER_QTY,
+    PRICE,
+    ORD_TYPE,
+    TIME_IN_FORCE,
+];
+
+/// Parses a New Order Single message into an [`Order`].
+///
+/// # Errors:
+/// - Returns [`MalformedField`](crate::error::FixError)
+/// - Returns [`UnknownTag`](crate::error::FixError)
+/// - Returns [`DuplicateTag`](crate::error::FixError)
+/// - Returns [`MissingTag`](crate::error::FixError)
+/// - Returns [`InvalidValue`](crate::error::FixError)
+pub fn parse_new_order_single(message: &str) -> FixResult<Order> {
+    let fields = parse_fields(message)?;
+
+    let msg_type = required(&fields, MSG_TYPE)?;
+    if msg_type != "D" {
+        return Err(invalid(MSG_TYPE, msg_type));
+    }
+    let ord_type = required(&fields, ORD_TYPE)?;
+    if ord_type != "2" {
+        return Err(invalid(ORD_TYPE, ord_type));
+    }
+
+    let side = match required(&fields, SIDE)? {
+        "1" => Side::Buy,
+        "2" => Side::Sell,
+        value => return Err(invalid(SIDE, value)),
+    };
+    let order_type = match fields.get(&TIME_IN_FORCE).copied().unwrap_or("0") {
+        "0" => OrderType::GoodForDay,
+        "1" => OrderType::GoodTillCancel,
+        "3" => OrderType::FillAndKill,
+        value => return Err(invalid(TIME_IN_FORCE, value)),
+    };
+
+    Ok(Order::new(
+        order_type,
+        parse_value(&fields, CL_ORD_ID)?,
+        side,
+        parse_value(&fields, PRICE)?,
+        parse_value(&fields, ORDER_QTY)?,
+    ))
+}
+
+/// Splits a message into its fields, keyed by tag.
+fn parse_fields(message: &str) -> FixResult<BTreeMap<u32, &str>> {
+    let mut fields = BTreeMap::new();
+    for field in message
+        .split(['|', '\x01'])
+        .filter(|field| !field.is_empty())
+    {
+        let (tag, value) = field
+            .split_once('=')
+            .ok_or_else(|| FixError::MalformedField(field.to_string()))?;
+        let tag: u32 = tag
+            .parse()
+            .map_err(|_| FixError::MalformedField(field.to_string()))?;
+        if !SUPPORTED_TAGS.contains(&tag) {
+            return Err(FixError::UnknownTag(tag));
+        }
+        if fields.insert(tag, value).is_some() {
+            return Err(FixError::DuplicateTag(tag));
+        }
+    }
+    Ok(fields)
+}
+
+fn required<'a>(fields: &BTreeMap<u32, &'a str>, tag: u32) -> FixResult<&'a str> {
+    fields.get(&tag).copied().ok_or(FixError::MissingTag(tag))
+}
+
+fn parse_value<T: FromStr>(fields: &BTreeMap<u32, &str>, tag: u32) -> FixResult<T> {
+    let value = required(fields, tag)?;
+    value.parse().map_err(|_| invalid(tag, value))
+}
+
+fn invalid(tag: u32, value: &str) -> FixError {
+    FixError::InvalidValue {
+        tag,
+        value: value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_new_order_single() {
+        let order = parse_new_order_single("35=D|54=1|38=100|44=10000|40=2|11=7").unwrap();
+        assert_eq!(
+            order,
+            Order::new(OrderType::GoodForDay, 7, Side::Buy, 10000, 100)
+        );
+
+        // SOH separated, with a time in force and a trailing separator
+        let order =
+            parse_new_order_single("35=D\x0111=8\x0154=2\x0138=5\x0144=-25\x0140=2\x0159=3\x01")
+                .unwrap();
+        assert_eq!(
+            order,
+            Order::new(OrderType::FillAndKill, 8, Side::Sell, -25, 5)
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_messages() {
+        let cases = [
+            ("35=D|54=1|38=100|44=10000|40=2", FixError::MissingTag(11)),
+            (
+                "35=D|54=1|38=100|44=10000|40=2|11=7|1=ACCT",
+                FixError::UnknownTag(1),
+            ),
+            (
+                "35=D|54=1|38=100|44=10000|40=2|11=7|11=8",
+                FixError::DuplicateTag(11),
+            ),
+            (
+                "35=D|54=1|38=100|44=10000|40=2|11",
+                FixError::MalformedField("11".to_string()),
+            ),
+            (
+                "35=D|54=1|38=100|44=10000|40=2|x=7",
+                FixError::MalformedField("x=7".to_string()),
+            ),
+            (
+                "35=F|54=1|38=100|44=10000|40=2|11=7",
+                invalid(MSG_TYPE, "F"),
+            ),
+            (
+                "35=D|54=1|38=100|44=10000|40=1|11=7",
+                invalid(ORD_TYPE, "1"),
+            ),
+            ("35=D|54=3|38=100|44=10000|40=2|11=7", invalid(SIDE, "3")),
+            (
+                "35=D|54=1|38=-5|44=10000|40=2|11=7",
+                invalid(ORDER_QTY, "-5"),
+            ),
+            (
+                "35=D|54=1|38=100|44=100.5|40=2|11=7",
+                invalid(PRICE, "100.5"),
+            ),
+            (
+                "35=D|54=1|38=100|44=10000|40=2|11=7|59=6",
+                invalid(TIME_IN_FORCE, "6"),
+            ),
+        ];
+        for (message, err) in cases {
+            assert_eq!(parse_new_order_single(message), Err(err), "{message}");
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Conversions between the book's types and external message formats.
+
+pub mod fix;
+#[cfg(feature = "tcp")]
+pub mod tcp;
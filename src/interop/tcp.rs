@@ -0,0 +1,123 @@
+//! Tiny line based TCP server around an [`OrderBook`], for integration demos. Each command is
+//! one line and each response ends with an `OK` or `ERR <reason>` line:
+//!
+//! | command | response |
+//! |---------|----------|
+//! | `ADD <id> <B\|S> <qty> <price> <GTC\|GFD\|FAK>` | a `TRADE <bid id> <ask id> <qty> <price>` line per trade |
+//! | `CANCEL <id>` | nothing besides `OK` |
+//! | `BOOK` | an `ASK <price> <qty>` line per ask level, highest first, then a `BID <price> <qty>` line per bid level |
+//!
+//! Connections are served one at a time, so the book never needs to be shared.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+};
+
+use crate::{
+    order::{Order, OrderType, Side},
+    orderbook::OrderBook,
+};
+
+/// Serves connections from `listener` against `book` until accepting a connection fails.
+///
+/// # Errors:
+/// - Returns the [`io::Error`] of a failed accept
+pub fn serve(listener: TcpListener, mut book: OrderBook) -> io::Result<()> {
+    for stream in listener.incoming() {
+        // a client dropping its connection mid command shouldn't stop the server
+        let _ = handle_connection(stream?, &mut book);
+    }
+    Ok(())
+}
+
+/// Answers commands from one client until it disconnects.
+///
+/// # Errors:
+/// - Returns the [`io::Error`] of a failed read or write
+pub fn handle_connection(stream: TcpStream, book: &mut OrderBook) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match execute(book, &line) {
+            Ok(mut lines) => {
+                lines.push("OK".to_string());
+                lines
+            }
+            Err(reason) => vec![format!("ERR {reason}")],
+        };
+        for response_line in response {
+            writeln!(writer, "{response_line}")?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Runs one command, returning its response lines without the closing `OK`.
+fn execute(book: &mut OrderBook, line: &str) -> Result<Vec<String>, String> {
+    let mut args = line.split_whitespace();
+    let command = args.next().unwrap_or_default();
+    let mut lines = Vec::new();
+    match command {
+        "ADD" => {
+            let order_id = parse(args.next(), "id")?;
+            let side = match args.next() {
+                Some("B") => Side::Buy,
+                Some("S") => Side::Sell,
+                side => return Err(format!("invalid side {}", side.unwrap_or_default())),
+            };
+            let quantity = parse(args.next(), "quantity")?;
+            let price = parse(args.next(), "price")?;
+            let order_type = match args.next() {
+                Some("GTC") => OrderType::GoodTillCancel,
+                Some("GFD") => OrderType::GoodForDay,
+                Some("FAK") => OrderType::FillAndKill,
+                order_type => {
+                    return Err(format!(
+                        "invalid order type {}",
+                        order_type.unwrap_or_default()
+                    ))
+                }
+            };
+            let trades = book
+                .submit(Order::new(order_type, order_id, side, price, quantity))
+                .map_err(|err| err.to_string())?;
+            for trade in trades.unwrap_or_default() {
+                lines.push(format!(
+                    "TRADE {} {} {} {}",
+                    trade.get_bid_trade().order_id,
+                    trade.get_ask_trade().order_id,
+                    trade.get_quantity(),
+                    trade.get_price()
+                ));
+            }
+        }
+        "CANCEL" => {
+            book.cancel_order(parse(args.next(), "id")?)
+                .map_err(|err| err.to_string())?;
+        }
+        "BOOK" => {
+            for (price, quantity) in book.ask_ladder().into_iter().rev() {
+                lines.push(format!("ASK {price} {quantity}"));
+            }
+            for (price, quantity) in book.bid_ladder() {
+                lines.push(format!("BID {price} {quantity}"));
+            }
+        }
+        _ => return Err(format!("unknown command {command}")),
+    }
+    match args.next() {
+        Some(extra) => Err(format!("unexpected argument {extra}")),
+        None => Ok(lines),
+    }
+}
+
+fn parse<T: FromStr>(arg: Option<&str>, name: &str) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| format!("missing {name}"))?;
+    arg.parse().map_err(|_| format!("invalid {name} {arg}"))
+}
@@ -1,4 +1,10 @@
+pub mod allocation;
 pub mod error;
+pub mod events;
+pub mod interop;
+pub mod multibook;
 pub mod order;
 pub mod orderbook;
+pub mod throttle;
 pub mod trade;
+pub mod wire;
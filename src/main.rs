@@ -5,8 +5,25 @@
  * Still a WIP
  *****************************************************/
 use orderbook::{error::BookResult, order::*, orderbook::*};
-use std::{cell::RefCell, rc::Rc};
 
 fn main() -> BookResult<()> {
+    let mut book = OrderBook::new("QQQ");
+
+    book.submit(Order::new(
+        OrderType::GoodTillCancel,
+        1,
+        Side::Buy,
+        10000,
+        100,
+    ))?;
+    book.submit(Order::new(
+        OrderType::GoodTillCancel,
+        2,
+        Side::Sell,
+        10000,
+        40,
+    ))?;
+
+    println!("{:?}", book.get_order_infos());
     Ok(())
 }
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::{BookResult, OrderBookError::UnknownSymbol},
+    order::Order,
+    orderbook::OrderBook,
+    trade::Trades,
+};
+
+/// One [`OrderBook`] per symbol, with orders routed to the book of their symbol.
+#[derive(Default)]
+pub struct MultiBook {
+    books: HashMap<&'static str, OrderBook>,
+    // create a book for an unknown symbol on first use, instead of rejecting the order
+    auto_create: bool,
+}
+
+impl MultiBook {
+    /// Creates an empty set of books that rejects orders for symbols it has no book for.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_auto_create(&self) -> bool {
+        self.auto_create
+    }
+
+    /// Switches between creating books for unknown symbols on first use and rejecting them.
+    pub fn set_auto_create(&mut self, auto_create: bool) {
+        self.auto_create = auto_create;
+    }
+
+    /// Book for `symbol`, created empty if there isn't one yet.
+    pub fn add_book(&mut self, symbol: &'static str) -> &mut OrderBook {
+        self.books
+            .entry(symbol)
+            .or_insert_with(|| OrderBook::new(symbol))
+    }
+
+    pub fn get_book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol)
+    }
+    pub fn get_book_mut(&mut self, symbol: &str) -> Option<&mut OrderBook> {
+        self.books.get_mut(symbol)
+    }
+
+    /// Symbols with a book, in no particular order.
+    pub fn symbols(&self) -> Vec<&'static str> {
+        self.books.keys().copied().collect()
+    }
+
+    /// Adds an order to the book for `symbol` and provides resulting Trades.
+    ///
+    /// # Errors:
+    /// - Returns [`UnknownSymbol`](crate::error::OrderBookError) if there's no book for `symbol`
+    ///   and auto-create is off
+    /// - Same as [`OrderBook::submit`]
+    pub fn route_order(
+        &mut self,
+        symbol: &'static str,
+        order: Order,
+    ) -> BookResult<Option<Trades>> {
+        let book = match self.auto_create {
+            true => self.add_book(symbol),
+            false => self
+                .books
+                .get_mut(symbol)
+                .ok_or_else(|| UnknownSymbol(symbol.to_string()))?,
+        };
+        book.submit(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::{OrderType, Side};
+
+    fn gtc(order_id: i64, side: Side) -> Order {
+        Order::new(OrderType::GoodTillCancel, order_id, side, 100, 10)
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_unknown_symbol() -> BookResult<()> {
+        let mut books = MultiBook::new();
+        books.add_book("QQQ");
+
+        assert!(books.route_order("QQQ", gtc(1, Side::Buy))?.is_none());
+        let err = books.route_order("SPY", gtc(2, Side::Sell)).unwrap_err();
+        assert!(matches!(&err, UnknownSymbol(symbol) if symbol == "SPY"));
+        assert!(err.to_string().contains("SPY"));
+        assert!(books.get_book("SPY").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_create_mode_creates_book() -> BookResult<()> {
+        let mut books = MultiBook::new();
+        books.set_auto_create(true);
+
+        assert!(books.route_order("SPY", gtc(1, Side::Buy))?.is_none());
+        let trades = books.route_order("SPY", gtc(2, Side::Sell))?;
+        assert_eq!(trades.map(|trades| trades.len()), Some(1));
+
+        let book = books.get_book("SPY").unwrap();
+        assert_eq!(book.get_asset(), "SPY");
+        assert_eq!(books.symbols(), vec!["SPY"]);
+        Ok(())
+    }
+}
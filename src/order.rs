@@ -1,30 +1,104 @@
-use crate::error::{
-    BookResult, OrdResult,
-    OrderBookError::*,
-    OrderError::{self, *},
+use crate::{
+    error::{OrdResult, OrderError::*, WireError, WireResult},
+    wire::{WireReader, WireWriter},
 };
 
 use linked_hash_map::LinkedHashMap;
-use std::{cell::RefCell, fmt::Display, rc::Rc, sync::{Arc, Mutex}};
+use std::{
+    cmp::min,
+    sync::{Arc, Mutex},
+};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Ordered for reports from the shortest lived type to the longest,
+/// `FillAndKill < GoodForCount < GoodForDay < GoodTillCancel < GoodAfterTime < PegMid`, with
+/// pegged orders last.
+/// GoodForCount and GoodAfterTime orders are further ordered by their count and time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum OrderType {
     // grab whatever is immediately available and get out
     FillAndKill,
+    // cancelled by the book's expiry sweep once this many later orders have been accepted
+    GoodForCount(u64),
+    // cleared by the book's end of day routine
+    GoodForDay,
     // typically cleared after 30 - 60 days
     GoodTillCancel,
+    // held out of the book until it's activated at this time, then rests like GoodTillCancel
+    GoodAfterTime(u64),
+    // priced by the book at the midpoint, following it as the top of book moves
+    PegMid,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Side {
     Buy,
     Sell,
 }
 
+impl Side {
+    pub fn opposite(&self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+}
+
+/// Whether `a` is a better price than `b` for an order on `side`.
+/// Higher is better for bids, lower is better for asks.
+pub fn is_better_price(side: Side, a: Price, b: Price) -> bool {
+    match side {
+        Side::Buy => a > b,
+        Side::Sell => a < b,
+    }
+}
+
 /// Bid or Ask price for an Order. Unit is cents.
+/// Prices may be negative (e.g. spread products) and are ordered as plain signed integers.
 pub type Price = i32;
 pub type Quantity = u32;
 pub type OrderId = i64;
+/// Client/participant an order belongs to. Orders without one belong to owner 0.
+pub type OwnerId = u64;
+
+/// Digits after the decimal point when writing a [`Price`] in dollars.
+pub const PRICE_DECIMALS: usize = 2;
+
+/// Parses a dollar amount such as `"100.50"` or `"-0.5"` into a [`Price`] in cents.
+///
+/// # Errors:
+/// Returns [`InvalidPrice`](crate::error::OrderError) if the input isn't a decimal number,
+/// has more than [`PRICE_DECIMALS`] decimal places or doesn't fit in a [`Price`].
+pub fn price_from_decimal_str(price: &str) -> OrdResult<Price> {
+    let invalid = || InvalidPrice(price.to_string());
+
+    let (negative, digits) = match price.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, price),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(invalid());
+    }
+    if fraction.len() > PRICE_DECIMALS {
+        return Err(invalid());
+    }
+
+    // pad the fraction to whole cents, e.g. "5" -> "50"
+    let cents = format!("{whole}{fraction:0<PRICE_DECIMALS$}");
+    let cents: i64 = cents.parse().map_err(|_| invalid())?;
+    let cents = if negative { -cents } else { cents };
+    Price::try_from(cents).map_err(|_| invalid())
+}
+
+/// Writes a [`Price`] in cents as a dollar amount, e.g. `10050` as `"100.50"`.
+pub fn price_to_decimal_string(price: Price) -> String {
+    let sign = if price < 0 { "-" } else { "" };
+    let cents = price.unsigned_abs();
+    let scale = 10u32.pow(PRICE_DECIMALS as u32);
+    format!("{sign}{}.{:0PRICE_DECIMALS$}", cents / scale, cents % scale)
+}
 
 /// Represents an order sent to an Exchange.
 #[derive(Debug, Clone, PartialEq)]
@@ -35,6 +109,14 @@ pub struct Order {
     price: Price,
     initial_quantity: Quantity,
     remaining_quantity: Quantity,
+    owner: OwnerId,
+    // reserve orders only show up to display_quantity, the rest stays hidden
+    display_quantity: Option<Quantity>,
+    hidden_quantity: Quantity,
+    // opaque client metadata, e.g. a strategy name, copied onto the order's fills
+    tag: Option<Arc<str>>,
+    // only trades as far as it reduces the owner's position
+    reduce_only: bool,
 }
 
 impl Order {
@@ -52,9 +134,48 @@ impl Order {
             price,
             initial_quantity: quantity,
             remaining_quantity: quantity,
+            owner: 0,
+            display_quantity: None,
+            hidden_quantity: 0,
+            tag: None,
+            reduce_only: false,
         }
     }
 
+    /// Turns the order into a reserve order showing at most `display_quantity` at a time.
+    /// The rest is held back and shown as the visible part fills.
+    pub fn with_reserve(mut self, display_quantity: Quantity) -> Self {
+        let total_quantity = self.remaining_quantity + self.hidden_quantity;
+        self.display_quantity = Some(display_quantity);
+        self.remaining_quantity = min(display_quantity, total_quantity);
+        self.hidden_quantity = total_quantity - self.remaining_quantity;
+        self
+    }
+
+    /// Moves the order to a new price, for the book to re-price pegged orders.
+    pub(crate) fn set_price(&mut self, price: Price) {
+        self.price = price;
+    }
+
+    /// Attributes the order to an owner.
+    pub fn with_owner(mut self, owner: OwnerId) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// Makes the order reduce only: at match time it trades only as far as it brings its
+    /// owner's position toward flat, and whatever would grow the position is cancelled.
+    pub fn with_reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+
+    /// Tags the order with client metadata, carried onto its fills. The book never reads it.
+    pub fn with_tag(mut self, tag: impl Into<Arc<str>>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
     pub fn get_order_type(&self) -> &OrderType {
         &self.order_type
     }
@@ -73,13 +194,49 @@ impl Order {
     pub fn get_remaining_quantity(&self) -> &Quantity {
         &self.remaining_quantity
     }
+    pub fn get_owner(&self) -> &OwnerId {
+        &self.owner
+    }
+    pub fn get_tag(&self) -> &Option<Arc<str>> {
+        &self.tag
+    }
+    pub fn is_reduce_only(&self) -> bool {
+        self.reduce_only
+    }
+    pub fn get_display_quantity(&self) -> &Option<Quantity> {
+        &self.display_quantity
+    }
+    pub fn get_hidden_quantity(&self) -> &Quantity {
+        &self.hidden_quantity
+    }
     pub fn get_filled_quantity(&self) -> Quantity {
-        self.initial_quantity - self.remaining_quantity
+        self.initial_quantity - self.remaining_quantity - self.hidden_quantity
+    }
+    /// Share of the initial quantity filled so far, in `[0, 1]`. 0 for an order without quantity.
+    pub fn fill_ratio(&self) -> f64 {
+        match self.initial_quantity {
+            0 => 0.0,
+            initial_quantity => self.get_filled_quantity() as f64 / initial_quantity as f64,
+        }
     }
+    /// Whether the visible quantity is used up. A reserve order may still have hidden quantity.
     pub fn is_filled(&self) -> bool {
         self.remaining_quantity == 0
     }
 
+    /// Shows the next slice of a reserve order once its visible quantity is used up.
+    /// Returns whether anything was drawn from the reserve.
+    pub fn refresh_from_reserve(&mut self) -> bool {
+        match self.display_quantity {
+            Some(display_quantity) if self.is_filled() && self.hidden_quantity > 0 => {
+                self.remaining_quantity = min(display_quantity, self.hidden_quantity);
+                self.hidden_quantity -= self.remaining_quantity;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Fills the order.
     ///
     /// # Errors:
@@ -87,6 +244,7 @@ impl Order {
     pub fn fill(&mut self, quantity: Quantity) -> OrdResult<()> {
         if quantity > *self.get_remaining_quantity() {
             return Err(RequestedFillTooLarge {
+                order_id: self.order_id,
                 surplus: quantity - *self.get_remaining_quantity(),
             });
         }
@@ -94,9 +252,180 @@ impl Order {
         Ok(())
     }
 
+    /// Fills up to `quantity`, stopping once nothing remains, and returns the quantity actually
+    /// filled. For best-effort reconstruction from feeds that may report more than the local
+    /// order has left, where [`fill`](Self::fill) would error.
+    pub fn fill_saturating(&mut self, quantity: Quantity) -> Quantity {
+        let filled = min(quantity, self.remaining_quantity);
+        self.remaining_quantity -= filled;
+        filled
+    }
+
+    /// Lowers the order's total quantity to `quantity`, taking the reduction out of the hidden
+    /// reserve before the visible quantity. The caller ensures `quantity` is below the initial
+    /// quantity and above the filled quantity.
+    pub(crate) fn reduce_to(&mut self, quantity: Quantity) {
+        let reduction = self.initial_quantity - quantity;
+        let from_hidden = min(reduction, self.hidden_quantity);
+        self.hidden_quantity -= from_hidden;
+        self.remaining_quantity -= reduction - from_hidden;
+        self.initial_quantity = quantity;
+    }
+
     pub fn to_order_ref(self) -> OrderRef {
         Arc::new(Mutex::new(self))
     }
+
+    /// Encodes the order in the binary wire format, all fields little-endian:
+    ///
+    /// | offset | size | field |
+    /// |--------|------|-------|
+    /// | 0  | 1 | version |
+    /// | 1  | 1 | order type: 0 FillAndKill, 1 GoodTillCancel, 2 GoodForDay, 3 PegMid, 4 GoodForCount, 5 GoodAfterTime |
+    /// | 2  | 1 | side: 0 Buy, 1 Sell |
+    /// | 3  | 1 | flags: 1 if reserve order, 2 if reduce only |
+    /// | 4  | 8 | order id |
+    /// | 12 | 4 | price |
+    /// | 16 | 4 | initial quantity |
+    /// | 20 | 4 | remaining quantity |
+    /// | 24 | 4 | hidden quantity |
+    /// | 28 | 4 | display quantity, 0 unless reserve order |
+    /// | 32 | 8 | owner |
+    /// | 40 | 8 | GoodForCount's count or GoodAfterTime's activation time, else 0 |
+    ///
+    /// The tag isn't encoded, decoded orders come back untagged.
+    pub fn to_bytes(&self) -> [u8; ORDER_WIRE_LEN] {
+        let (order_type, type_value): (u8, u64) = match self.order_type {
+            OrderType::FillAndKill => (0, 0),
+            OrderType::GoodTillCancel => (1, 0),
+            OrderType::GoodForDay => (2, 0),
+            OrderType::PegMid => (3, 0),
+            OrderType::GoodForCount(count) => (4, count),
+            OrderType::GoodAfterTime(activation_time) => (5, activation_time),
+        };
+        WireWriter::new()
+            .put(&[order_type, side_tag(self.side)])
+            .put(&[self.display_quantity.is_some() as u8 | (self.reduce_only as u8) << 1])
+            .put(&self.order_id.to_le_bytes())
+            .put(&self.price.to_le_bytes())
+            .put(&self.initial_quantity.to_le_bytes())
+            .put(&self.remaining_quantity.to_le_bytes())
+            .put(&self.hidden_quantity.to_le_bytes())
+            .put(&self.display_quantity.unwrap_or(0).to_le_bytes())
+            .put(&self.owner.to_le_bytes())
+            .put(&type_value.to_le_bytes())
+            .finish()
+    }
+
+    /// Decodes an order written by [`to_bytes`](Self::to_bytes). Bytes past the order are ignored.
+    ///
+    /// # Errors:
+    /// - Returns [`Truncated`](crate::error::WireError)
+    /// - Returns [`UnsupportedVersion`](crate::error::WireError)
+    /// - Returns [`InvalidTag`](crate::error::WireError)
+    /// - Returns [`QuantityExceedsInitial`](crate::error::WireError)
+    /// - Returns [`ZeroDisplayQuantity`](crate::error::WireError)
+    /// - Returns [`HiddenWithoutReserve`](crate::error::WireError)
+    /// - Returns [`RemainingExceedsDisplay`](crate::error::WireError)
+    pub fn from_bytes(bytes: &[u8]) -> WireResult<Self> {
+        let mut reader = WireReader::new(bytes, ORDER_WIRE_LEN)?;
+        let order_type_tag = reader.u8();
+        let side = side_from_tag(reader.u8())?;
+        let flags = match reader.u8() {
+            value if value > 3 => {
+                return Err(WireError::InvalidTag {
+                    field: "flags",
+                    value,
+                })
+            }
+            value => value,
+        };
+        let order_id = reader.i64();
+        let price = reader.i32();
+        let initial_quantity = reader.u32();
+        let remaining_quantity = reader.u32();
+        let hidden_quantity = reader.u32();
+        let display_quantity = reader.u32();
+        let owner = reader.u64();
+        let type_value = reader.u64();
+
+        // anything else would leave the order's filled quantity negative
+        if remaining_quantity as u64 + hidden_quantity as u64 > initial_quantity as u64 {
+            return Err(WireError::QuantityExceedsInitial {
+                initial: initial_quantity,
+                remaining: remaining_quantity,
+                hidden: hidden_quantity,
+            });
+        }
+        // only reserve orders hide quantity, and never show more than their display quantity
+        if flags & 1 == 0 {
+            if hidden_quantity > 0 {
+                return Err(WireError::HiddenWithoutReserve(hidden_quantity));
+            }
+        } else if display_quantity == 0 {
+            return Err(WireError::ZeroDisplayQuantity);
+        } else if remaining_quantity > display_quantity {
+            return Err(WireError::RemainingExceedsDisplay {
+                remaining: remaining_quantity,
+                display: display_quantity,
+            });
+        }
+
+        let order_type = match order_type_tag {
+            0 => OrderType::FillAndKill,
+            1 => OrderType::GoodTillCancel,
+            2 => OrderType::GoodForDay,
+            3 => OrderType::PegMid,
+            4 => OrderType::GoodForCount(type_value),
+            5 => OrderType::GoodAfterTime(type_value),
+            value => {
+                return Err(WireError::InvalidTag {
+                    field: "order type",
+                    value,
+                })
+            }
+        };
+
+        Ok(Self {
+            order_type,
+            order_id,
+            side,
+            price,
+            initial_quantity,
+            remaining_quantity,
+            owner,
+            display_quantity: (flags & 1 != 0).then_some(display_quantity),
+            hidden_quantity,
+            tag: None,
+            reduce_only: flags & 2 != 0,
+        })
+    }
+}
+
+/// Length of an [`Order`] in the binary wire format.
+pub const ORDER_WIRE_LEN: usize = 48;
+
+/// Wire format tag of a side.
+fn side_tag(side: Side) -> u8 {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+/// Side for a wire format tag.
+///
+/// # Errors:
+/// - Returns [`InvalidTag`](crate::error::WireError)
+fn side_from_tag(value: u8) -> WireResult<Side> {
+    match value {
+        0 => Ok(Side::Buy),
+        1 => Ok(Side::Sell),
+        value => Err(WireError::InvalidTag {
+            field: "side",
+            value,
+        }),
+    }
 }
 
 pub type OrderRef = Arc<Mutex<Order>>;
@@ -109,6 +438,10 @@ pub struct OrderModify {
     side: Option<Side>,
     price: Option<Price>,
     quantity: Option<Quantity>,
+    // reject the modify instead of letting the order take liquidity
+    post_only: bool,
+    // client assigned, increasing across modifies of the same order
+    client_seq: Option<u64>,
 }
 
 impl OrderModify {
@@ -123,9 +456,24 @@ impl OrderModify {
             side,
             price,
             quantity,
+            post_only: false,
+            client_seq: None,
         }
     }
 
+    /// Makes the book reject the modify if the modified order would cross the opposite side.
+    pub fn with_post_only(mut self) -> Self {
+        self.post_only = true;
+        self
+    }
+
+    /// Tags the modify with a client sequence, so the book rejects it if a modify with an
+    /// equal or higher sequence was already applied to the order.
+    pub fn with_client_seq(mut self, client_seq: u64) -> Self {
+        self.client_seq = Some(client_seq);
+        self
+    }
+
     pub fn get_order_id(&self) -> &OrderId {
         &self.order_id
     }
@@ -138,7 +486,20 @@ impl OrderModify {
     pub fn get_quantity(&self) -> &Option<Quantity> {
         &self.quantity
     }
+    pub fn is_post_only(&self) -> bool {
+        self.post_only
+    }
+    pub fn get_client_seq(&self) -> &Option<u64> {
+        &self.client_seq
+    }
 
+    /// Builds the modified order. The quantity is the new total quantity, so whatever the
+    /// original already filled stays filled and only the rest is left to trade.
+    ///
+    /// # Errors:
+    /// - Returns [`ModificationError`](crate::error::OrderError) if the order ids differ
+    /// - Returns [`ModificationError`](crate::error::OrderError) if the new quantity doesn't
+    ///   exceed the quantity already filled
     pub fn to_order(&self, order_to_modify: Order) -> OrdResult<Order> {
         if order_to_modify.get_order_id() != self.get_order_id() {
             return Err(ModificationError(format!(
@@ -163,19 +524,38 @@ impl OrderModify {
             None => *order_to_modify.get_initial_quantity(),
         };
 
-        Ok(Order::new(
+        let filled_quantity = order_to_modify.get_filled_quantity();
+        if new_quantity <= filled_quantity {
+            return Err(ModificationError(format!(
+                "Order {} already filled {}, can't reduce its quantity to {}...",
+                self.order_id, filled_quantity, new_quantity
+            )));
+        }
+
+        let mut order = Order::new(
             *order_to_modify.get_order_type(),
             self.order_id,
             new_side,
             new_price,
             new_quantity,
-        ))
+        )
+        .with_owner(*order_to_modify.get_owner());
+        order.tag = order_to_modify.tag.clone();
+        order.reduce_only = order_to_modify.reduce_only;
+        order.remaining_quantity -= filled_quantity;
+
+        Ok(match order_to_modify.get_display_quantity() {
+            Some(display_quantity) => order.with_reserve(*display_quantity),
+            None => order,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::OrderError;
+    use std::collections::HashMap;
 
     // Order
 
@@ -217,10 +597,103 @@ mod tests {
 
         assert_eq!(
             order.fill(130),
-            Err(OrderError::RequestedFillTooLarge { surplus: 30 })
+            Err(OrderError::RequestedFillTooLarge {
+                order_id: 101212,
+                surplus: 30
+            })
         );
     }
 
+    #[test]
+    fn test_reserve_order_refresh() -> OrdResult<()> {
+        let mut order =
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 30, 50).with_reserve(20);
+        assert_eq!(*order.get_remaining_quantity(), 20);
+        assert_eq!(*order.get_hidden_quantity(), 30);
+        assert!(!order.refresh_from_reserve());
+
+        order.fill(20)?;
+        assert!(order.refresh_from_reserve());
+        assert_eq!(*order.get_remaining_quantity(), 20);
+        assert_eq!(*order.get_hidden_quantity(), 10);
+
+        order.fill(20)?;
+        assert!(order.refresh_from_reserve());
+        assert_eq!(*order.get_remaining_quantity(), 10);
+        assert_eq!(order.get_filled_quantity(), 40);
+
+        order.fill(10)?;
+        assert!(!order.refresh_from_reserve());
+        assert_eq!(order.get_filled_quantity(), 50);
+        Ok(())
+    }
+
+    // Side / OrderType
+
+    #[test]
+    fn test_side_and_order_type_as_map_keys() {
+        let mut quantity_by_side: HashMap<Side, Quantity> = HashMap::new();
+        *quantity_by_side.entry(Side::Buy).or_default() += 10;
+        *quantity_by_side.entry(Side::Buy).or_default() += 5;
+        *quantity_by_side.entry(Side::Sell).or_default() += 7;
+
+        assert_eq!(quantity_by_side[&Side::Buy], 15);
+        assert_eq!(quantity_by_side[&Side::Sell], 7);
+
+        let mut count_by_kind: HashMap<(Side, OrderType), usize> = HashMap::new();
+        *count_by_kind
+            .entry((Side::Buy, OrderType::FillAndKill))
+            .or_default() += 1;
+        assert_eq!(count_by_kind[&(Side::Buy, OrderType::FillAndKill)], 1);
+        assert!(!count_by_kind.contains_key(&(Side::Sell, OrderType::FillAndKill)));
+    }
+
+    #[test]
+    fn test_order_type_ordering() {
+        let mut order_types = vec![
+            OrderType::PegMid,
+            OrderType::GoodTillCancel,
+            OrderType::GoodForCount(10),
+            OrderType::FillAndKill,
+            OrderType::GoodForDay,
+            OrderType::GoodForCount(2),
+            OrderType::GoodAfterTime(5),
+        ];
+        order_types.sort();
+        assert_eq!(
+            order_types,
+            vec![
+                OrderType::FillAndKill,
+                OrderType::GoodForCount(2),
+                OrderType::GoodForCount(10),
+                OrderType::GoodForDay,
+                OrderType::GoodTillCancel,
+                OrderType::GoodAfterTime(5),
+                OrderType::PegMid,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_better_price() {
+        assert!(is_better_price(Side::Buy, 101, 100));
+        assert!(!is_better_price(Side::Buy, 100, 101));
+        assert!(!is_better_price(Side::Buy, 100, 100));
+
+        assert!(is_better_price(Side::Sell, 100, 101));
+        assert!(!is_better_price(Side::Sell, 101, 100));
+        assert!(!is_better_price(Side::Sell, 100, 100));
+
+        assert!(is_better_price(Side::Buy, -5, -10));
+        assert!(is_better_price(Side::Sell, -10, -5));
+    }
+
+    #[test]
+    fn test_opposite_side() {
+        assert_eq!(Side::Buy.opposite(), Side::Sell);
+        assert_eq!(Side::Sell.opposite(), Side::Buy);
+    }
+
     // OrderModify
 
     #[test]
@@ -268,6 +741,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_modify_order_keeps_owner() -> OrdResult<()> {
+        let order_to_modify =
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 30, 100).with_owner(42);
+
+        let order = OrderModify::new(1, None, Some(31), None).to_order(order_to_modify)?;
+
+        assert_eq!(*order.get_owner(), 42);
+        Ok(())
+    }
+
     #[test]
     fn test_modify_order_mismatched_ids() {
         let order_to_modify = Order::new(
@@ -284,4 +768,191 @@ mod tests {
 
         assert!(matches!(order, Err(OrderError::ModificationError(_))));
     }
+
+    #[test]
+    fn test_order_wire_round_trip() {
+        let orders = [
+            Order::new(OrderType::GoodTillCancel, 7, Side::Buy, -250, 100),
+            Order::new(OrderType::GoodForDay, i64::MAX, Side::Sell, 10_000, 90)
+                .with_owner(42)
+                .with_reserve(25),
+            Order::new(OrderType::GoodForCount(u64::MAX), 3, Side::Buy, 99, 10),
+            Order::new(OrderType::GoodAfterTime(1_000), 5, Side::Buy, 99, 10),
+            Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 99, 10)
+                .with_reserve(5)
+                .with_reduce_only(),
+        ];
+        for mut order in orders {
+            order.fill(5).unwrap();
+            let bytes = order.to_bytes();
+            assert_eq!(bytes[0], crate::wire::WIRE_VERSION);
+            assert_eq!(Order::from_bytes(&bytes), Ok(order));
+        }
+    }
+
+    #[test]
+    fn test_order_wire_decode_errors() {
+        let bytes = Order::new(OrderType::FillAndKill, 1, Side::Buy, 100, 10).to_bytes();
+
+        assert_eq!(
+            Order::from_bytes(&bytes[..ORDER_WIRE_LEN - 1]),
+            Err(WireError::Truncated {
+                expected: ORDER_WIRE_LEN,
+                actual: ORDER_WIRE_LEN - 1
+            })
+        );
+        assert!(matches!(
+            Order::from_bytes(&[]),
+            Err(WireError::Truncated { .. })
+        ));
+
+        let mut bad_version = bytes;
+        bad_version[0] = 9;
+        assert_eq!(
+            Order::from_bytes(&bad_version),
+            Err(WireError::UnsupportedVersion(9))
+        );
+
+        let mut bad_side = bytes;
+        bad_side[2] = 2;
+        assert_eq!(
+            Order::from_bytes(&bad_side),
+            Err(WireError::InvalidTag {
+                field: "side",
+                value: 2
+            })
+        );
+
+        let mut bad_flags = bytes;
+        bad_flags[3] = 4;
+        assert_eq!(
+            Order::from_bytes(&bad_flags),
+            Err(WireError::InvalidTag {
+                field: "flags",
+                value: 4
+            })
+        );
+
+        // remaining quantity of 50 on an order of 10
+        let mut bad_remaining = bytes;
+        bad_remaining[20..24].copy_from_slice(&50u32.to_le_bytes());
+        assert_eq!(
+            Order::from_bytes(&bad_remaining),
+            Err(WireError::QuantityExceedsInitial {
+                initial: 10,
+                remaining: 50,
+                hidden: 0
+            })
+        );
+
+        // reserve flag set without a display quantity
+        let mut bad_display = bytes;
+        bad_display[3] = 1;
+        assert_eq!(
+            Order::from_bytes(&bad_display),
+            Err(WireError::ZeroDisplayQuantity)
+        );
+
+        // 5 of the 10 lots hidden without a reserve to refresh them from
+        let mut bad_hidden = bytes;
+        bad_hidden[20..24].copy_from_slice(&5u32.to_le_bytes());
+        bad_hidden[24..28].copy_from_slice(&5u32.to_le_bytes());
+        assert_eq!(
+            Order::from_bytes(&bad_hidden),
+            Err(WireError::HiddenWithoutReserve(5))
+        );
+
+        // a reserve order showing all 10 lots behind a display quantity of 4
+        let mut bad_shown = bytes;
+        bad_shown[3] = 1;
+        bad_shown[28..32].copy_from_slice(&4u32.to_le_bytes());
+        assert_eq!(
+            Order::from_bytes(&bad_shown),
+            Err(WireError::RemainingExceedsDisplay {
+                remaining: 10,
+                display: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_price_from_decimal_str() {
+        assert_eq!(price_from_decimal_str("100.50"), Ok(10050));
+        assert_eq!(price_from_decimal_str("100.5"), Ok(10050));
+        assert_eq!(price_from_decimal_str("100"), Ok(10000));
+        assert_eq!(price_from_decimal_str("0.07"), Ok(7));
+        assert_eq!(price_from_decimal_str("-1.25"), Ok(-125));
+
+        for price in ["", ".5", "-", "abc", "1.2.3", "+1", "99999999999"] {
+            assert!(
+                matches!(price_from_decimal_str(price), Err(InvalidPrice(_))),
+                "{price}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_price_over_precision_rejected() {
+        assert_eq!(
+            price_from_decimal_str("100.505"),
+            Err(InvalidPrice("100.505".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_price_to_decimal_string() {
+        assert_eq!(price_to_decimal_string(10050), "100.50");
+        assert_eq!(price_to_decimal_string(7), "0.07");
+        assert_eq!(price_to_decimal_string(0), "0.00");
+        assert_eq!(price_to_decimal_string(-125), "-1.25");
+        assert_eq!(price_to_decimal_string(Price::MIN), "-21474836.48");
+
+        for price in [10050, -3, Price::MAX] {
+            assert_eq!(
+                price_from_decimal_str(&price_to_decimal_string(price)),
+                Ok(price)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fill_saturating() {
+        let mut order = Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 100);
+
+        assert_eq!(order.fill_saturating(130), 100);
+        assert_eq!(*order.get_remaining_quantity(), 0);
+        assert!(order.is_filled());
+        assert_eq!(order.fill_saturating(5), 0);
+    }
+
+    #[test]
+    fn test_modify_keeps_filled_quantity() {
+        let mut order = Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 100);
+        order.fill(40).unwrap();
+
+        let modified = OrderModify::new(1, None, Some(101), Some(50))
+            .to_order(order.clone())
+            .unwrap();
+        assert_eq!(*modified.get_initial_quantity(), 50);
+        assert_eq!(*modified.get_remaining_quantity(), 10);
+
+        let modified = OrderModify::new(1, None, None, Some(40)).to_order(order);
+        assert!(matches!(modified, Err(OrderError::ModificationError(_))));
+    }
+
+    #[test]
+    fn test_fill_ratio() -> OrdResult<()> {
+        let mut order =
+            Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 100, 50).with_reserve(10);
+        assert_eq!(order.fill_ratio(), 0.0);
+
+        order.fill(10)?;
+        order.refresh_from_reserve();
+        order.fill(10)?;
+        assert_eq!(order.fill_ratio(), 0.4);
+
+        let empty = Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 100, 0);
+        assert_eq!(empty.fill_ratio(), 0.0);
+        Ok(())
+    }
 }
@@ -1,24 +1,57 @@
 use std::{
     cmp::min,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Write,
     mem,
+    ops::Bound::{Excluded, Unbounded},
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc, MutexGuard, PoisonError,
+    },
 };
 
 use crate::{
-    error::BookResult, error::OrderBookError::*, error::OrderBookError::*, order::*, trade::*,
+    allocation::{pro_rata, Rounding},
+    error::BookResult,
+    error::OrderBookError::{self, *},
+    events::BookEvent,
+    order::*,
+    throttle::RateLimiter,
+    trade::*,
 };
 
 use linked_hash_map::LinkedHashMap;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct LevelInfo {
     price: Price,
     quantity: Quantity,
+    // number of orders resting at the level
+    order_count: usize,
+}
+
+impl LevelInfo {
+    pub fn new(price: Price, quantity: Quantity, order_count: usize) -> Self {
+        Self {
+            price,
+            quantity,
+            order_count,
+        }
+    }
+    pub fn get_price(&self) -> &Price {
+        &self.price
+    }
+    pub fn get_quantity(&self) -> &Quantity {
+        &self.quantity
+    }
+    pub fn get_order_count(&self) -> &usize {
+        &self.order_count
+    }
 }
 
 pub type LevelInfos = Vec<LevelInfo>;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct OrderBookLevelInfos {
     bids: LevelInfos,
     asks: LevelInfos,
@@ -39,19 +72,244 @@ impl OrderBookLevelInfos {
     }
 }
 
+/// Immutable copy of the book's aggregated levels at one point in time.
+/// Readers can hold on to it, across threads, while the live book keeps matching.
+#[derive(Debug)]
+pub struct BookSnapshot {
+    asset: &'static str,
+    level_infos: OrderBookLevelInfos,
+}
+
+impl BookSnapshot {
+    pub fn get_asset(&self) -> &'static str {
+        self.asset
+    }
+    pub fn get_bids(&self) -> &LevelInfos {
+        self.level_infos.get_bids()
+    }
+    pub fn get_asks(&self) -> &LevelInfos {
+        self.level_infos.get_asks()
+    }
+}
+
+/// Outcome of sweeping the book with a hypothetical aggressive order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepResult {
+    // (price, quantity filled at that price), in the order levels are hit
+    fills: Vec<(Price, Quantity)>,
+    filled_quantity: Quantity,
+    unfilled_quantity: Quantity,
+}
+
+impl SweepResult {
+    pub fn get_fills(&self) -> &Vec<(Price, Quantity)> {
+        &self.fills
+    }
+    pub fn get_filled_quantity(&self) -> &Quantity {
+        &self.filled_quantity
+    }
+    pub fn get_unfilled_quantity(&self) -> &Quantity {
+        &self.unfilled_quantity
+    }
+
+    /// Average price of the simulated fills, weighted by quantity. None if nothing filled.
+    pub fn vwap(&self) -> Option<f64> {
+        if self.filled_quantity == 0 {
+            return None;
+        }
+        let notional: i128 = self
+            .fills
+            .iter()
+            .map(|(price, quantity)| *price as i128 * *quantity as i128)
+            .sum();
+        Some(notional as f64 / self.filled_quantity as f64)
+    }
+}
+
+/// An owner's net position and average fill price, see [`OrderBook::fill_report`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FillReportEntry {
+    pub owner: OwnerId,
+    pub net_quantity: i64,
+    pub vwap: f64,
+}
+
+/// How an added order fared as the aggressor, see [`OrderBook::add_order_with_result`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddResult {
+    // quantity the order traded on arrival, and its average execution price
+    pub aggressor_filled: Quantity,
+    pub aggressor_vwap: Option<f64>,
+    // quantity left unfilled, hidden reserve included, whether or not it rests
+    pub residual: Quantity,
+    pub resting: bool,
+    pub trades: Trades,
+}
+
+/// What a modify did to the order, see [`OrderBook::modify_order`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModifyResult {
+    // nothing to change, the order kept everything including its place in the queue
+    Unchanged,
+    // modified down to no more than it had filled, so whatever was left got cancelled
+    Cancelled,
+    // modified, with any trades it made
+    Amended(Option<Trades>),
+}
+
+/// Trading phase of the book.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Phase {
+    // incoming orders match immediately
+    #[default]
+    Continuous,
+    // incoming orders only rest, matching waits for an uncross
+    Auction,
+    // trading halted, incoming orders only rest until the book resumes
+    Halted,
+}
+
+/// Decides which resting order at a price level is matched first.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LevelOrdering {
+    // earliest order first
+    #[default]
+    Time,
+    // largest remaining quantity first, earliest order on ties. Sizes are ranked once, as a
+    // sweep reaches the level, rather than before every fill
+    SizeThenTime,
+}
+
+/// What the book does when a bid and an ask of the same owner meet.
+/// Orders without an owner (owner 0) are never treated as matching themselves.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StpMode {
+    // let them trade
+    #[default]
+    None,
+    // cancel the order that was resting, the aggressor carries on
+    CancelResting,
+    // cancel the aggressor, keeping whatever it already filled
+    CancelNewest,
+    // cancel both orders
+    CancelBoth,
+}
+
+/// Decides the price a matched bid and ask trade at.
+/// The midpoint is of the best bid and ask as they were before the operation that matched.
+/// Between ticks, it's rounded to the tick in the resting order's favour, see
+/// [`OrderBook::set_tick_size`]. Without both sides there is no midpoint, and trades fall back
+/// to the resting order's price. Neither order ever trades beyond its own limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExecutionPrice {
+    // the price of whichever order rested first
+    #[default]
+    RestingPrice,
+    // the midpoint, unless that's beyond either order's limit, then the nearer limit
+    Midpoint,
+    // same as Midpoint, which now also caps at both limits
+    MidpointCappedByLimit,
+}
+
+/// Called with the new best bid and best ask whenever either changes.
+pub type TopOfBookListener = Box<dyn FnMut(Option<Price>, Option<Price>) + Send>;
+
+/// Called with every trade as the book produces it.
+pub type TradeListener = Box<dyn FnMut(TradeView<'_>) + Send>;
+
+/// Called with the aggressor, the resting order and the quantity before each fill.
+/// Returning false vetoes the fill.
+pub type PreTradeHook = Box<dyn FnMut(&Order, &Order, Quantity) -> bool + Send>;
+
 /// Keeps track of Order's location in book.
 struct OrderEntry {
     book_side: Side,
     price: Price,
     order_id: OrderId,
+    // position in the sequence of orders accepted by the book
+    seq: u64,
+    // sequence the order entered the book with, kept when it's requeued
+    inserted_seq: u64,
+    // client sequence of the last modify applied to the order
+    last_client_seq: Option<u64>,
+    // quantity traded by orders ahead of it in its level since it joined the queue
+    filled_ahead: Quantity,
+    // mirror of the order's remaining quantity, so market data queries can read it without
+    // locking the order. Only written under &mut self, the lock still holds the real value
+    remaining_quantity: AtomicU64,
 }
 
+/// Number of filled (and, in strict mode, cancelled) order ids the book remembers.
+const RECENT_ORDER_IDS_CAPACITY: usize = 1024;
+
+/// Width, in characters, of the largest level's bar in [`OrderBook::to_ascii_heatmap`].
+const HEATMAP_BAR_WIDTH: u64 = 40;
+
 /// An Orderbook ordered according to price time priority.
 pub struct OrderBook {
     asset: &'static str,
     bid_side: BTreeMap<Price, OrderRefs>,
     ask_side: BTreeMap<Price, OrderRefs>,
     track_orders: HashMap<OrderId, OrderEntry>,
+    // oldest at the front, evicted once capacity is reached
+    recently_filled: LinkedHashMap<OrderId, ()>,
+    // only filled in strict mode, same eviction as recently_filled
+    recently_cancelled: LinkedHashMap<OrderId, ()>,
+    strict_order_ids: bool,
+    // reject, rather than drop, FillAndKill orders arriving at an empty opposite side
+    reject_on_empty_opposite: bool,
+    // keep showing emptied levels with zero quantity until prune_empty_levels
+    sticky_levels: bool,
+    // levels emptied since the last prune, only collected with sticky_levels on
+    emptied_levels: BTreeSet<(Side, Price)>,
+    // levels the outermost running mutation created, which were never displayed
+    created_levels: HashSet<(Side, Price)>,
+    max_order_quantity: Option<Quantity>,
+    // fills a single match pass makes before yielding, None for no limit
+    max_fills_per_match: Option<usize>,
+    // throttles adds and modifies per owner when set
+    rate_limiter: Option<RateLimiter>,
+    level_ordering: LevelOrdering,
+    stp_mode: StpMode,
+    execution_price: ExecutionPrice,
+    // how pro-rata allocations hand out what flooring leaves over
+    rounding: Rounding,
+    // grid computed prices are rounded to
+    tick_size: Price,
+    phase: Phase,
+    next_seq: u64,
+    total_matched_quantity: u64,
+    total_matched_notional: i128,
+    // net quantity each owner bought, negative when they sold more than they bought
+    positions: HashMap<OwnerId, i64>,
+    // every trade since creation, oldest first
+    trade_history: Trades,
+    // positions in trade_history of the trades each order id took part in
+    trade_indices: HashMap<OrderId, Vec<usize>>,
+    // None unless event recording is switched on
+    events: Option<Vec<BookEvent>>,
+    // resting order ids consumed, only collected by match_orders_traced
+    #[cfg(debug_assertions)]
+    match_trace: Option<Vec<OrderId>>,
+    top_of_book_listener: Option<TopOfBookListener>,
+    trade_listener: Option<TradeListener>,
+    pre_trade_hook: Option<PreTradeHook>,
+    // best bid and ask the listener last heard about
+    last_top_of_book: (Option<Price>, Option<Price>),
+    // set while a public mutation runs, so nested ones don't notify midway
+    mutating: bool,
+    // best bid and ask as the outermost running mutation found them
+    pre_mutation_top_of_book: (Option<Price>, Option<Price>),
+    // PegMid orders resting in a level, may include ids that have since left the book
+    pegged_ids: HashSet<OrderId>,
+    // PegMid orders waiting for a midpoint, not part of any level
+    parked_pegs: LinkedHashMap<OrderId, OrderRef>,
+    // GoodAfterTime orders waiting for their activation time, not part of any level
+    scheduled_orders: LinkedHashMap<OrderId, OrderRef>,
+    // FillAndKill orders placed during an auction or halt, cancelled once the uncross is over
+    held_faks: Vec<OrderId>,
+    // latest time handed to activate_scheduled
+    scheduled_clock: u64,
 }
 
 impl OrderBook {
@@ -61,21 +319,554 @@ impl OrderBook {
             bid_side: BTreeMap::new(),
             ask_side: BTreeMap::new(),
             track_orders: HashMap::new(),
+            recently_filled: LinkedHashMap::new(),
+            recently_cancelled: LinkedHashMap::new(),
+            strict_order_ids: false,
+            reject_on_empty_opposite: false,
+            sticky_levels: false,
+            emptied_levels: BTreeSet::new(),
+            created_levels: HashSet::new(),
+            max_order_quantity: None,
+            max_fills_per_match: None,
+            rate_limiter: None,
+            level_ordering: LevelOrdering::default(),
+            stp_mode: StpMode::default(),
+            execution_price: ExecutionPrice::default(),
+            rounding: Rounding::default(),
+            tick_size: 1,
+            phase: Phase::default(),
+            next_seq: 0,
+            total_matched_quantity: 0,
+            total_matched_notional: 0,
+            positions: HashMap::new(),
+            trade_history: vec![],
+            trade_indices: HashMap::new(),
+            events: None,
+            #[cfg(debug_assertions)]
+            match_trace: None,
+            top_of_book_listener: None,
+            trade_listener: None,
+            pre_trade_hook: None,
+            last_top_of_book: (None, None),
+            mutating: false,
+            pre_mutation_top_of_book: (None, None),
+            pegged_ids: HashSet::new(),
+            parked_pegs: LinkedHashMap::new(),
+            scheduled_orders: LinkedHashMap::new(),
+            held_faks: vec![],
+            scheduled_clock: 0,
+        }
+    }
+
+    /// Creates a book with its order tracking pre-sized for `expected_orders` resting orders,
+    /// avoiding rehashes during the first burst of orders. Price levels live in `BTreeMap`s,
+    /// which can't be pre-sized, so `_expected_levels` is only a hint for now.
+    pub fn with_capacity(
+        asset: &'static str,
+        _expected_levels: usize,
+        expected_orders: usize,
+    ) -> Self {
+        Self {
+            track_orders: HashMap::with_capacity(expected_orders),
+            ..Self::new(asset)
+        }
+    }
+
+    pub fn get_asset(&self) -> &'static str {
+        self.asset
+    }
+    pub fn get_level_ordering(&self) -> &LevelOrdering {
+        &self.level_ordering
+    }
+    pub fn get_stp_mode(&self) -> &StpMode {
+        &self.stp_mode
+    }
+    pub fn get_execution_price(&self) -> &ExecutionPrice {
+        &self.execution_price
+    }
+    pub fn get_rounding(&self) -> &Rounding {
+        &self.rounding
+    }
+    pub fn get_tick_size(&self) -> &Price {
+        &self.tick_size
+    }
+    pub fn get_max_fills_per_match(&self) -> &Option<usize> {
+        &self.max_fills_per_match
+    }
+
+    pub fn get_phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// Sequence the next order accepted into a level will be given.
+    pub fn get_next_seq(&self) -> &u64 {
+        &self.next_seq
+    }
+
+    /// Switches the book's trading phase.
+    /// Moving from [`Phase::Auction`] or [`Phase::Halted`] back to [`Phase::Continuous`] runs a
+    /// match pass over everything that accumulated, returning the resulting trades.
+    /// FillAndKill orders placed in the meantime are cancelled after that pass if unfilled.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn set_phase(&mut self, phase: Phase) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| {
+            let previous_phase = mem::replace(&mut book.phase, phase);
+            match (previous_phase, phase) {
+                (Phase::Auction | Phase::Halted, Phase::Continuous) => book.uncross_held(),
+                _ => Ok(None),
+            }
+        })
+    }
+
+    /// Halts trading. Orders are still accepted, crossing ones included, but only rest until
+    /// [`resume`](Self::resume).
+    pub fn halt(&mut self) {
+        self.phase = Phase::Halted;
+    }
+
+    /// Resumes continuous trading, matching whatever crossed during the halt in a single pass.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn resume(&mut self) -> BookResult<Option<Trades>> {
+        self.set_phase(Phase::Continuous)
+    }
+
+    /// Matches any crossed orders in the book, regardless of phase. FillAndKill orders placed
+    /// during an auction or halt are cancelled after the pass if unfilled.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn uncross(&mut self) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| book.uncross_held())
+    }
+
+    /// Total quantity matched by the book since creation.
+    pub fn traded_volume(&self) -> u64 {
+        self.total_matched_quantity
+    }
+
+    /// Total value, in cents, of everything matched by the book since creation.
+    /// Each fill is valued at the price of the order that was resting.
+    pub fn traded_notional(&self) -> i128 {
+        self.total_matched_notional
+    }
+
+    /// Net quantity `owner` has bought across every trade, negative when they sold more than
+    /// they bought.
+    pub fn position(&self, owner: OwnerId) -> i64 {
+        self.positions.get(&owner).copied().unwrap_or(0)
+    }
+
+    /// Every trade the book has produced, oldest first.
+    pub fn get_trade_history(&self) -> &Trades {
+        &self.trade_history
+    }
+
+    /// Every fill of `order_id`, oldest first, each with `price` set to the price it executed
+    /// at. Fills of an earlier order that used the same id are included.
+    pub fn order_fills(&self, order_id: OrderId) -> Vec<TradeInfo> {
+        self.trade_indices
+            .get(&order_id)
+            .into_iter()
+            .flatten()
+            .map(|index| {
+                let trade = &self.trade_history[*index];
+                let fill = match trade.get_bid_trade().order_id == order_id {
+                    true => trade.get_bid_trade(),
+                    false => trade.get_ask_trade(),
+                };
+                TradeInfo {
+                    price: *trade.get_price(),
+                    ..fill.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Traded quantity at each execution price, over the whole trade history.
+    pub fn volume_by_price(&self) -> BTreeMap<Price, u64> {
+        let mut volumes: BTreeMap<Price, u64> = BTreeMap::new();
+        for trade in &self.trade_history {
+            *volumes.entry(*trade.get_price()).or_default() += *trade.get_quantity() as u64;
+        }
+        volumes
+    }
+
+    /// Starts or stops recording a [`BookEvent`] for every change to the resting orders.
+    /// Switching it off discards anything not yet drained.
+    pub fn set_event_recording(&mut self, enabled: bool) {
+        if !enabled {
+            self.events = None;
+        } else if self.events.is_none() {
+            self.events = Some(vec![]);
+        }
+    }
+
+    /// Sets a listener called with the new `(best bid, best ask)` after any operation that moves
+    /// either of them. Operations leaving the top of book as it was don't call it.
+    pub fn set_top_of_book_listener(&mut self, listener: Option<TopOfBookListener>) {
+        self.top_of_book_listener = listener;
+        self.last_top_of_book = (self.best_bid(), self.best_ask());
+    }
+
+    /// Sets a listener called with a view of each trade as soon as it happens, before the
+    /// operation producing it returns.
+    pub fn set_trade_listener(&mut self, listener: Option<TradeListener>) {
+        self.trade_listener = listener;
+    }
+
+    /// Sets a hook checked before each fill, e.g. for credit checks between counterparties.
+    /// When it vetoes a fill, the resting order is skipped for the rest of that match and the
+    /// aggressor moves on to the next resting order. Both orders stay in the book.
+    pub fn set_pre_trade_hook(&mut self, hook: Option<PreTradeHook>) {
+        self.pre_trade_hook = hook;
+    }
+
+    /// Takes the events recorded since the last drain, oldest first.
+    pub fn drain_events(&mut self) -> Vec<BookEvent> {
+        self.events.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    /// Net position and average fill price of every owner that traded, ordered by owner.
+    /// Buys count as positive quantity, sells as negative.
+    pub fn fill_report(&self) -> Vec<FillReportEntry> {
+        // owner -> (net quantity, filled quantity, filled notional)
+        let mut totals: BTreeMap<OwnerId, (i64, u64, i128)> = BTreeMap::new();
+        for trade in &self.trade_history {
+            for (fill, direction) in [(trade.get_bid_trade(), 1), (trade.get_ask_trade(), -1)] {
+                let (net_quantity, quantity, notional) = totals.entry(fill.owner).or_default();
+                *net_quantity += direction * fill.quantity as i64;
+                *quantity += fill.quantity as u64;
+                *notional += trade.get_notional();
+            }
+        }
+
+        totals
+            .into_iter()
+            .map(
+                |(owner, (net_quantity, quantity, notional))| FillReportEntry {
+                    owner,
+                    net_quantity,
+                    vwap: notional as f64 / quantity as f64,
+                },
+            )
+            .collect()
+    }
+
+    /// Spread `owner` captured over the trade history: their quantity weighted average sell
+    /// price minus their average buy price, positive when they sold above where they bought.
+    /// Returns None unless the owner has both bought and sold.
+    pub fn realized_spread(&self, owner: OwnerId) -> Option<f64> {
+        // (quantity, notional) bought and sold
+        let (mut bought, mut sold) = ((0u64, 0i128), (0u64, 0i128));
+        for trade in &self.trade_history {
+            for (fill, totals) in [
+                (trade.get_bid_trade(), &mut bought),
+                (trade.get_ask_trade(), &mut sold),
+            ] {
+                if fill.owner == owner {
+                    totals.0 += fill.quantity as u64;
+                    totals.1 += trade.get_notional();
+                }
+            }
+        }
+
+        let average = |(quantity, notional): (u64, i128)| {
+            (quantity > 0).then(|| notional as f64 / quantity as f64)
+        };
+        Some(average(sold)? - average(bought)?)
+    }
+
+    /// In strict mode, ids of recently filled or cancelled orders can't be reused by new orders.
+    /// Off by default; only a bounded number of recent ids is remembered either way.
+    pub fn set_strict_order_ids(&mut self, strict_order_ids: bool) {
+        self.strict_order_ids = strict_order_ids;
+    }
+
+    /// When on, a FillAndKill order arriving while the opposite side of the book is empty is
+    /// rejected with [`NoLiquidity`](crate::error::OrderBookError) instead of quietly dropped.
+    /// FillAndKill orders that just don't cross are still dropped without an error.
+    pub fn set_reject_on_empty_opposite(&mut self, reject_on_empty_opposite: bool) {
+        self.reject_on_empty_opposite = reject_on_empty_opposite;
+    }
+
+    /// When on, a level that empties keeps showing in [`get_order_infos`](Self::get_order_infos)
+    /// with zero quantity until [`prune_empty_levels`](Self::prune_empty_levels), so displays
+    /// don't flicker. Only the display is affected, matching never sees the emptied levels.
+    /// Switching it off prunes them.
+    pub fn set_sticky_levels(&mut self, sticky_levels: bool) {
+        self.sticky_levels = sticky_levels;
+        if !sticky_levels {
+            self.prune_empty_levels();
+        }
+    }
+
+    /// Drops the zero quantity levels kept by [`set_sticky_levels`](Self::set_sticky_levels).
+    pub fn prune_empty_levels(&mut self) {
+        self.emptied_levels.clear();
+    }
+
+    /// Caps the quantity of any single order added or modified, as a fat finger check.
+    /// `None` removes the cap. Orders already resting are unaffected.
+    pub fn set_max_order_quantity(&mut self, max_order_quantity: Option<Quantity>) {
+        self.max_order_quantity = max_order_quantity;
+    }
+
+    /// Bounds the work a single operation spends matching: a match pass yields after
+    /// `max_fills_per_match` fills, leaving the rest for [`continue_matching`](Self::continue_matching).
+    /// The book may stay crossed between the two, but a FillAndKill order's chance to trade ends
+    /// with the add placing it, which cancels what it left unfilled even if the pass yielded
+    /// before reaching it. `None` removes the limit.
+    pub fn set_max_fills_per_match(&mut self, max_fills_per_match: Option<usize>) {
+        self.max_fills_per_match = max_fills_per_match;
+    }
+
+    /// Resumes matching a book left crossed by a match pass that hit
+    /// [`set_max_fills_per_match`](Self::set_max_fills_per_match)'s limit, again making at most
+    /// that many fills. Returns None once nothing is left to match.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn continue_matching(&mut self) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| {
+            if book.phase != Phase::Continuous {
+                return Ok(None);
+            }
+            book.match_orders()
+        })
+    }
+
+    /// Throttles [`add_order`](Self::add_order) and [`modify_order`](Self::modify_order) per
+    /// owner. Each one takes a token from its owner's bucket, whether or not it then succeeds.
+    /// `None` removes the limit.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<RateLimiter>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    /// Sets how resting orders within a price level are prioritised.
+    /// Levels stay in time order internally, so this can change at any point.
+    pub fn set_level_ordering(&mut self, level_ordering: LevelOrdering) {
+        self.level_ordering = level_ordering;
+    }
+
+    /// Sets the rule deciding the price matched orders trade at.
+    pub fn set_execution_price(&mut self, execution_price: ExecutionPrice) {
+        self.execution_price = execution_price;
+    }
+
+    /// Sets how [`pro_rata_allocation`](Self::pro_rata_allocation) hands out the lots left
+    /// over after flooring proportional shares.
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.rounding = rounding;
+    }
+
+    /// Sets the tick size, in cents, that prices the book computes land on: midpoint execution
+    /// prices and pegged order prices. Defaults to 1. Prices of incoming orders aren't checked.
+    ///
+    /// # Errors:
+    /// - Returns [`InvalidPrice`](crate::error::OrderBookError) if `tick_size` isn't positive
+    pub fn set_tick_size(&mut self, tick_size: Price) -> BookResult<()> {
+        if tick_size <= 0 {
+            return Err(InvalidPrice(format!("tick size {tick_size}")));
         }
+        self.tick_size = tick_size;
+        Ok(())
+    }
+
+    /// Sets the self-match prevention applied when a bid and an ask of the same owner meet.
+    pub fn set_stp_mode(&mut self, stp_mode: StpMode) {
+        self.stp_mode = stp_mode;
     }
 
     /// Adds an Order to the OrderBook and provides resulting Trades.
     ///
     /// # Errors:
     /// - Returns [`OrderAlreadyExists`](crate::error::OrderBookError)
-    /// - Returns [`InternalOrderProcessingError`](crate::error::OrderBookError)
+    /// - Returns [`OrderIdRecentlyUsed`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`NoLiquidity`](crate::error::OrderBookError)
+    /// - Returns [`RateLimited`](crate::error::OrderBookError)
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    /// - Returns [`LockPoisoned`](crate::error::OrderBookError)
     pub fn add_order(&mut self, order: OrderRef) -> BookResult<Option<Trades>> {
-        let order_ref = order.lock().unwrap();
+        let mut trades: Trades = vec![];
+        self.add_order_into(order, &mut trades)?;
+        Ok((!trades.is_empty()).then_some(trades))
+    }
+
+    /// Adds an Order to the OrderBook like [`add_order`](Self::add_order), summarizing the
+    /// outcome from the added order's point of view alongside the trades.
+    ///
+    /// # Errors:
+    /// - Same as [`add_order`](Self::add_order)
+    pub fn add_order_with_result(&mut self, order: OrderRef) -> BookResult<AddResult> {
+        let order_id = *lock_order(&order)?.get_order_id();
+        let mut trades: Trades = vec![];
+        self.add_order_into(order.clone(), &mut trades)?;
+
+        let (mut aggressor_filled, mut notional) = (0, 0);
+        for trade in &trades {
+            if trade.get_bid_trade().order_id == order_id
+                || trade.get_ask_trade().order_id == order_id
+            {
+                aggressor_filled += *trade.get_quantity();
+                notional += trade.get_notional();
+            }
+        }
+        let residual = {
+            let order = read_order(&order);
+            order.get_remaining_quantity() + order.get_hidden_quantity()
+        };
+        Ok(AddResult {
+            aggressor_filled,
+            aggressor_vwap: (aggressor_filled > 0)
+                .then(|| notional as f64 / aggressor_filled as f64),
+            residual,
+            resting: self.get_order_ref(&order_id).is_ok(),
+            trades,
+        })
+    }
+
+    /// Adds an Order to the OrderBook, appending resulting Trades to a caller owned buffer.
+    /// Reusing one buffer across many adds avoids allocating a new one per add.
+    ///
+    /// # Errors:
+    /// - Same as [`add_order`](Self::add_order)
+    pub fn add_order_into(&mut self, order: OrderRef, out: &mut Vec<Trade>) -> BookResult<()> {
+        // adds made on behalf of another operation don't count against the rate limit
+        if !self.mutating {
+            let owner = *lock_order(&order)?.get_owner();
+            self.check_rate_limit(owner)?;
+        }
+        self.notifying_into(out, |book, out| {
+            if !book.insert_order(&order)? {
+                return Ok(());
+            }
+            let (order_id, side, price, is_fak) = {
+                let order = read_order(&order);
+                (
+                    *order.get_order_id(),
+                    *order.get_side(),
+                    *order.get_price(),
+                    matches!(order.get_order_type(), OrderType::FillAndKill),
+                )
+            };
+
+            // orders only rest during an auction or halt, matching waits for the uncross
+            if book.phase != Phase::Continuous {
+                if is_fak {
+                    book.held_faks.push(order_id);
+                }
+                return Ok(());
+            }
+
+            // typical passive add, nothing on the other side to trade with
+            if !book.can_match(&side, &price) {
+                return Ok(());
+            }
+
+            let matched = book.match_orders_into(out);
+            // the add was a FillAndKill's only chance to trade, even if the pass yielded before
+            // reaching it
+            let killed = match is_fak {
+                true => book.kill_unfilled_faks(vec![order_id]),
+                false => Ok(()),
+            };
+            matched?;
+            killed
+        })
+    }
+
+    /// Runs the checks [`add_order`](Self::add_order) applies to a new order, without placing
+    /// or matching it.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderAlreadyExists`](crate::error::OrderBookError)
+    /// - Returns [`OrderIdRecentlyUsed`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`NoLiquidity`](crate::error::OrderBookError)
+    pub fn validate_order(&self, order: &Order) -> BookResult<()> {
+        self.validate_replacement(order, None)
+    }
+
+    /// Runs [`validate_order`](Self::validate_order)'s checks as if the order `replaced` had
+    /// already left the book, so its replacement may take over its id.
+    fn validate_replacement(&self, order: &Order, replaced: Option<OrderId>) -> BookResult<()> {
+        let order_id = order.get_order_id();
+        if replaced != Some(*order_id) {
+            // check if order to add id exists in book
+            if self.track_orders.contains_key(order_id)
+                || self.parked_pegs.contains_key(order_id)
+                || self.scheduled_orders.contains_key(order_id)
+            {
+                return Err(OrderAlreadyExists(*order_id));
+            }
+            if self.strict_order_ids
+                && (self.recently_filled.contains_key(order_id)
+                    || self.recently_cancelled.contains_key(order_id))
+            {
+                return Err(OrderIdRecentlyUsed(*order_id));
+            }
+        }
+        self.check_order_quantity(order)?;
+
+        // reject the order if FaK and no liquidity available for it given current state of the
+        // book, where the replaced order doesn't count
+        if self.reject_on_empty_opposite
+            && matches!(order.get_order_type(), OrderType::FillAndKill)
+            && self
+                .side_levels(order.get_side().opposite())
+                .values()
+                .all(|orders| orders.keys().all(|order_id| Some(*order_id) == replaced))
+        {
+            return Err(NoLiquidity(*order_id));
+        }
+        Ok(())
+    }
 
-        // check if order to add id exists in book
-        let order_id = order_ref.get_order_id();
-        if self.track_orders.contains_key(order_id) {
-            return Err(OrderAlreadyExists(*order_id));
+    /// Places an order in its price level and starts tracking it, without matching.
+    /// Returns false if the order was rejected, or is a pegged order parked until there's a
+    /// midpoint or a GoodAfterTime order held until its activation time, without being placed.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderAlreadyExists`](crate::error::OrderBookError)
+    /// - Returns [`OrderIdRecentlyUsed`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`NoLiquidity`](crate::error::OrderBookError)
+    /// - Returns [`LockPoisoned`](crate::error::OrderBookError)
+    fn insert_order(&mut self, order: &OrderRef) -> BookResult<bool> {
+        let mut order_ref = lock_order(order)?;
+        self.validate_order(&order_ref)?;
+
+        if let OrderType::GoodAfterTime(activation_time) = *order_ref.get_order_type() {
+            if activation_time > self.scheduled_clock {
+                self.scheduled_orders
+                    .insert(*order_ref.get_order_id(), order.clone());
+                return Ok(false);
+            }
+        }
+
+        // pegged orders take the midpoint's price, or wait for one to exist
+        if let OrderType::PegMid = order_ref.get_order_type() {
+            match self.peg_price(*order_ref.get_side()) {
+                Some(price) => order_ref.set_price(price),
+                None => {
+                    self.parked_pegs
+                        .insert(*order_ref.get_order_id(), order.clone());
+                    return Ok(false);
+                }
+            }
+            self.pegged_ids.insert(*order_ref.get_order_id());
+        }
+
+        // drop the order if FaK and it can't trade given current state of the book
+        if let OrderType::FillAndKill = order_ref.get_order_type() {
+            if !self.can_match(order_ref.get_side(), order_ref.get_price()) {
+                return Ok(false);
+            }
         }
 
         // track order to add
@@ -85,15 +876,17 @@ impl OrderBook {
                 book_side: *order_ref.get_side(),
                 price: *order_ref.get_price(),
                 order_id: *order_ref.get_order_id(),
+                seq: self.next_seq,
+                inserted_seq: self.next_seq,
+                last_client_seq: None,
+                filled_ahead: 0,
+                remaining_quantity: AtomicU64::new(*order_ref.get_remaining_quantity() as u64),
             },
         );
-
-        // reject the order if FaK and no liquidity available for it given current state of the book
-        if let OrderType::FillAndKill = order_ref.get_order_type() {
-            if !self.can_match(order_ref.get_side(), order_ref.get_price()) {
-                return Ok(None);
-            }
-        }
+        self.next_seq += 1;
+        // id is live again, so it no longer refers to a filled or cancelled order
+        self.recently_filled.remove(order_ref.get_order_id());
+        self.recently_cancelled.remove(order_ref.get_order_id());
 
         // determine which side the order will be added to
         let book_side = match order_ref.get_side() {
@@ -108,25 +901,142 @@ impl OrderBook {
             let mut orders: OrderRefs = LinkedHashMap::new();
             orders.insert(*order_ref.get_order_id(), order.clone());
             book_side.insert(*order_ref.get_price(), orders);
+            if self.sticky_levels {
+                self.created_levels
+                    .insert((*order_ref.get_side(), *order_ref.get_price()));
+            }
         }
 
-        // taking the Rc reference out of scope
-        mem::drop(order_ref);
+        self.emit(BookEvent::OrderAdded {
+            order_id: *order_ref.get_order_id(),
+            side: *order_ref.get_side(),
+            price: *order_ref.get_price(),
+            quantity: *order_ref.get_remaining_quantity(),
+        });
+
+        Ok(true)
+    }
+
+    /// Adds an Order to the OrderBook, wrapping it into an [`OrderRef`] for the caller.
+    /// Use [`add_order`](Self::add_order) to keep a shared reference to the order.
+    ///
+    /// # Errors:
+    /// - Same as [`add_order`](Self::add_order)
+    pub fn submit(&mut self, order: Order) -> BookResult<Option<Trades>> {
+        self.add_order(order.to_order_ref())
+    }
+
+    /// Places a two-sided GoodTillCancel market for `owner`: a bid at `mid - half_spread` and an
+    /// ask at `mid + half_spread`, both of `size`. Both are validated before either is placed,
+    /// so a rejected quote leaves the book untouched. Then the bid is placed first.
+    ///
+    /// # Errors:
+    /// - Same as [`add_order`](Self::add_order)
+    pub fn quote(
+        &mut self,
+        owner: OwnerId,
+        bid_id: OrderId,
+        ask_id: OrderId,
+        mid: Price,
+        half_spread: Price,
+        size: Quantity,
+    ) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| {
+            let order = |order_id, side, price| {
+                Order::new(OrderType::GoodTillCancel, order_id, side, price, size).with_owner(owner)
+            };
+            let bid = order(bid_id, Side::Buy, mid - half_spread);
+            let ask = order(ask_id, Side::Sell, mid + half_spread);
+            book.validate_order(&bid)?;
+            book.validate_order(&ask)?;
+            if bid_id == ask_id {
+                return Err(OrderAlreadyExists(ask_id));
+            }
+
+            let mut trades: Trades = vec![];
+            book.add_order_into(bid.to_order_ref(), &mut trades)?;
+            book.add_order_into(ask.to_order_ref(), &mut trades)?;
+            Ok((!trades.is_empty()).then_some(trades))
+        })
+    }
 
-        // return trades!
-        Ok(self.match_orders()?)
+    /// Places pre-built resting orders, e.g. from a market snapshot, without matching them.
+    /// The book may be left crossed, in which case [`uncross`](Self::uncross) matches it.
+    /// Orders before a failing one stay loaded.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderAlreadyExists`](crate::error::OrderBookError)
+    /// - Returns [`OrderIdRecentlyUsed`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    pub fn load_resting(&mut self, orders: Vec<Order>) -> BookResult<()> {
+        self.notifying_into(&mut vec![], |book, _| {
+            for order in orders {
+                book.insert_order(&order.to_order_ref())?;
+            }
+            Ok(())
+        })
     }
 
     /// Remove an order from the book immediately.
     ///
     /// # Errors:
     /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    /// - Returns [`TrackingDesync`](crate::error::OrderBookError)
     pub fn cancel_order(&mut self, order_id: OrderId) -> BookResult<OrderId> {
+        self.notifying_into(&mut vec![], |book, _| {
+            book.remove_order(order_id)?;
+            if book.strict_order_ids {
+                remember_order_id(&mut book.recently_cancelled, order_id);
+            }
+            Ok(order_id)
+        })
+    }
+
+    /// Cancels each of `order_ids`, carrying on past ids that can't be cancelled.
+    /// Returns the cancelled ids and the ids not found in the book, both in the given order.
+    pub fn cancel_orders(&mut self, order_ids: &[OrderId]) -> (Vec<OrderId>, Vec<OrderId>) {
+        self.notifying(|book| {
+            order_ids
+                .iter()
+                .partition(|order_id| book.cancel_order(**order_id).is_ok())
+        })
+    }
+
+    /// Cancels each of `order_ids`, as a single operation, and returns the cancelled orders as
+    /// they were resting, in the given order, e.g. for a quote engine to reprice and resubmit.
+    /// Ids that can't be cancelled are skipped.
+    pub fn withdraw(&mut self, order_ids: &[OrderId]) -> Vec<Order> {
+        self.notifying(|book| {
+            order_ids
+                .iter()
+                .filter_map(|order_id| {
+                    let order = read_order(book.get_order_ref(order_id).ok()?).clone();
+                    book.cancel_order(*order_id).ok()?;
+                    Some(order)
+                })
+                .collect()
+        })
+    }
+
+    /// Takes an order out of the book without treating it as a client cancel.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    /// - Returns [`TrackingDesync`](crate::error::OrderBookError)
+    fn remove_order(&mut self, order_id: OrderId) -> BookResult<OrderId> {
+        if self.parked_pegs.remove(&order_id).is_some()
+            || self.scheduled_orders.remove(&order_id).is_some()
+        {
+            return Ok(order_id);
+        }
+
         // confirms order is in book
         let order_entry = self
             .track_orders
             .get(&order_id)
-            .ok_or(OrderNotFound(order_id))?;
+            .ok_or_else(|| self.missing_order_error(order_id))?;
 
         let book_side = match order_entry.book_side {
             Side::Buy => &mut self.bid_side,
@@ -135,301 +1045,4850 @@ impl OrderBook {
 
         let orders = book_side
             .get_mut(&order_entry.price)
-            .ok_or(OrderNotFound(order_id))?;
-        orders.remove(&order_entry.order_id);
+            .ok_or(TrackingDesync { order_id })?;
+        let removed = orders.remove(&order_entry.order_id);
 
-        orders
-            .is_empty()
-            .then(|| book_side.remove(&order_entry.price));
+        let emptied = orders.is_empty();
+        if emptied {
+            book_side.remove(&order_entry.price);
+        }
 
+        let (side, price) = (order_entry.book_side, order_entry.price);
         self.track_orders.remove(&order_id);
+        if emptied {
+            self.level_emptied(side, price);
+        }
+
+        if let Some(removed) = removed {
+            let quantity = *read_order(&removed).get_remaining_quantity();
+            self.emit(BookEvent::OrderRemoved {
+                order_id,
+                side,
+                price,
+                quantity,
+            });
+        }
 
         Ok(order_id)
     }
 
-    /// Modify order in book.
+    /// Modify order in book. The new quantity is a total, filled quantity included.
+    /// Whether the order keeps its place in the queue depends on what changes:
+    ///
+    /// | side and price | quantity | queue position |
+    /// |----------------|----------|----------------|
+    /// | same | same | kept, nothing changes |
+    /// | same | reduced | kept |
+    /// | same | increased | back of the level |
+    /// | changed | any | back of the new level, matching first if it crosses |
+    ///
+    /// A modify down to no more than the order already filled cancels whatever is left of it.
+    /// Trades of pegs re-priced by that cancel only show up in the trade history, as after
+    /// [`cancel_order`](Self::cancel_order).
     ///
     /// # Errors:
     /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
-    pub fn modify_order(&mut self, order: OrderModify) -> BookResult<Option<Trades>> {
-        let order_id = order.get_order_id();
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    /// - Returns [`TrackingDesync`](crate::error::OrderBookError)
+    /// - Returns [`ModificationError`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`WouldCross`](crate::error::OrderBookError) for a post only modify
+    /// - Returns [`StaleModification`](crate::error::OrderBookError)
+    /// - Returns [`RateLimited`](crate::error::OrderBookError)
+    /// - Returns [`LockPoisoned`](crate::error::OrderBookError)
+    pub fn modify_order(&mut self, order: OrderModify) -> BookResult<ModifyResult> {
+        let owner = self
+            .get_order_ref(order.get_order_id())
+            .ok()
+            .map(|order| *read_order(order).get_owner());
+        if let (false, Some(owner)) = (self.mutating, owner) {
+            self.check_rate_limit(owner)?;
+        }
+        let mut trades: Trades = vec![];
+        let result = self.notifying_into(&mut trades, |book, _| {
+            let order_id = order.get_order_id();
 
-        // confirms whether order exists
-        let old_order = self
-            .get_order_ref(order_id)?
-            .lock()
-            .unwrap()
-            // can't move Order out of Ref<'_, Order>, must clone
-            // to adhere to new OrderModify API
-            .clone();
+            // modifies carrying a client sequence must arrive in increasing order
+            let last_client_seq = book
+                .track_orders
+                .get(order_id)
+                .and_then(|order_entry| order_entry.last_client_seq);
+            if let (Some(client_seq), Some(last_client_seq)) =
+                (order.get_client_seq(), last_client_seq)
+            {
+                if *client_seq <= last_client_seq {
+                    return Err(StaleModification {
+                        order_id: *order_id,
+                        client_seq: *client_seq,
+                        last_client_seq,
+                    });
+                }
+            }
 
-        // ^ with curr impl, 2 clones needed to modify an Order ***
+            // confirms whether order exists
+            let old_order = lock_order(book.get_order_ref(order_id)?)?
+                // can't move Order out of Ref<'_, Order>, must clone
+                // to adhere to new OrderModify API
+                .clone();
 
-        self.cancel_order(*order_id)?;
+            // ^ with curr impl, 2 clones needed to modify an Order ***
 
-        self.add_order(order.to_order(old_order)?.to_order_ref())
-    }
+            // nothing to change, so the order keeps its place in the queue
+            let unchanged = order
+                .get_side()
+                .is_none_or(|side| side == *old_order.get_side())
+                && order
+                    .get_price()
+                    .is_none_or(|price| price == *old_order.get_price())
+                && order
+                    .get_quantity()
+                    .is_none_or(|quantity| quantity == *old_order.get_initial_quantity());
+            if unchanged {
+                if let Some(order_entry) = book.track_orders.get_mut(order_id) {
+                    order_entry.last_client_seq = order.get_client_seq().or(last_client_seq);
+                }
+                return Ok(ModifyResult::Unchanged);
+            }
+
+            // nothing would be left to trade, so the modify acts as a cancel
+            if order
+                .get_quantity()
+                .is_some_and(|quantity| quantity <= old_order.get_filled_quantity())
+            {
+                book.cancel_order(*order_id)?;
+                return Ok(ModifyResult::Cancelled);
+            }
+
+            // validate the modified order before the original leaves the book
+            let new_order = order.to_order(old_order.clone())?;
+            // the replacement must take the original's place, never another order's id
+            if new_order.get_order_id() != order_id {
+                return Err(ModificationError(format!(
+                    "Modified order has id {} instead of {}...",
+                    new_order.get_order_id(),
+                    order_id
+                )));
+            }
+            book.check_order_quantity(&new_order)?;
+            if order.is_post_only() && book.can_match(new_order.get_side(), new_order.get_price()) {
+                return Err(WouldCross {
+                    order_id: *order_id,
+                    price: *new_order.get_price(),
+                });
+            }
+
+            // a smaller total at the same price only gives up quantity, so it keeps its place
+            if new_order.get_side() == old_order.get_side()
+                && new_order.get_price() == old_order.get_price()
+                && new_order.get_initial_quantity() < old_order.get_initial_quantity()
+            {
+                book.reduce_order(&old_order, *new_order.get_initial_quantity())?;
+                if let Some(order_entry) = book.track_orders.get_mut(order_id) {
+                    order_entry.last_client_seq = order.get_client_seq().or(last_client_seq);
+                }
+                return Ok(ModifyResult::Amended(None));
+            }
+
+            book.remove_order(*order_id)?;
+
+            let trades = book.add_order(new_order.to_order_ref())?;
+            if let Some(order_entry) = book.track_orders.get_mut(order_id) {
+                order_entry.last_client_seq = order.get_client_seq().or(last_client_seq);
+            }
+            Ok(ModifyResult::Amended(trades))
+        })?;
+
+        // trades of pegs the amended order re-priced follow its own
+        Ok(match result {
+            ModifyResult::Amended(amended_trades) => {
+                let mut amended_trades = amended_trades.unwrap_or_default();
+                amended_trades.append(&mut trades);
+                ModifyResult::Amended((!amended_trades.is_empty()).then_some(amended_trades))
+            }
+            result => result,
+        })
+    }
+
+    /// Cancels an order and adds its replacement, which may reuse the old order's id, as one
+    /// operation. The replacement is checked as if the old order were already gone, and the old
+    /// order is left untouched if the replacement would be rejected.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyExists`](crate::error::OrderBookError)
+    /// - Returns [`OrderIdRecentlyUsed`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`NoLiquidity`](crate::error::OrderBookError)
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn cancel_replace(
+        &mut self,
+        old_id: OrderId,
+        new_order: Order,
+    ) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| {
+            // the same lookup cancel_order relies on, so parked and scheduled orders count
+            book.get_order_ref(&old_id)?;
+
+            let new_id = *new_order.get_order_id();
+            book.validate_replacement(&new_order, Some(old_id))?;
+
+            book.remove_order(old_id)?;
+            // an id taken over by the replacement isn't a cancelled one
+            if book.strict_order_ids && new_id != old_id {
+                remember_order_id(&mut book.recently_cancelled, old_id);
+            }
+            book.submit(new_order)
+        })
+    }
+
+    /// Replaces all of `owner`'s orders on `side` with `new_orders`, then matches once.
+    /// The new orders are attributed to `owner`. Each gets [`validate_order`](Self::validate_order)'s
+    /// checks, a new order may reuse the id of an order it replaces outside strict mode, and
+    /// nothing changes if any new order is invalid.
+    ///
+    /// # Errors:
+    /// - Returns [`ModificationError`](crate::error::OrderBookError) if a new order is on the other side
+    /// - Returns [`OrderAlreadyExists`](crate::error::OrderBookError)
+    /// - Returns [`OrderIdRecentlyUsed`](crate::error::OrderBookError)
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`NoLiquidity`](crate::error::OrderBookError)
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn replace_side(
+        &mut self,
+        side: Side,
+        owner: OwnerId,
+        new_orders: Vec<Order>,
+    ) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| {
+            let old_ids: Vec<OrderId> = book
+                .side_levels(side)
+                .values()
+                .flat_map(|orders| orders.values())
+                .filter_map(|order| {
+                    let order = read_order(order);
+                    (*order.get_owner() == owner).then_some(*order.get_order_id())
+                })
+                .collect();
+
+            // validate the whole batch before touching the book
+            let mut new_ids = HashSet::new();
+            for order in &new_orders {
+                let order_id = *order.get_order_id();
+                if *order.get_side() != side {
+                    return Err(ModificationError(format!(
+                        "Order {} is not on the {:?} side being replaced...",
+                        order_id, side
+                    )));
+                }
+                if !new_ids.insert(order_id) {
+                    return Err(OrderAlreadyExists(order_id));
+                }
+                let replaced = old_ids.contains(&order_id);
+                if replaced && book.strict_order_ids {
+                    return Err(OrderIdRecentlyUsed(order_id));
+                }
+                book.validate_replacement(order, replaced.then_some(order_id))?;
+            }
+
+            for order_id in old_ids {
+                book.cancel_order(order_id)?;
+            }
+            for order in new_orders {
+                book.insert_order(&order.with_owner(owner).to_order_ref())?;
+            }
+
+            if book.phase != Phase::Continuous {
+                return Ok(None);
+            }
+            book.match_orders()
+        })
+    }
+
+    /// Moves every GoodAfterTime order whose activation time is no later than `now` into the
+    /// book, in arrival order, and matches them. Until then they're held out of the book, so
+    /// they neither match nor show in its levels or [`open_order_ids`](Self::open_order_ids),
+    /// though they can be cancelled or modified. Orders arriving after their activation time
+    /// by the latest `now` seen go straight into the book. If any due order would now be
+    /// rejected, none are activated and they all stay scheduled.
+    ///
+    /// # Errors:
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    /// - Returns [`LockPoisoned`](crate::error::OrderBookError)
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    pub fn activate_scheduled(&mut self, now: u64) -> BookResult<Option<Trades>> {
+        self.notifying_trades(|book| {
+            book.scheduled_clock = book.scheduled_clock.max(now);
+            let due_ids: Vec<OrderId> = book
+                .scheduled_orders
+                .iter()
+                .filter(|(_, order)| {
+                    matches!(
+                        read_order(order).get_order_type(),
+                        OrderType::GoodAfterTime(activation_time) if *activation_time <= now
+                    )
+                })
+                .map(|(order_id, _)| *order_id)
+                .collect();
+
+            // the book's limits may have changed since the orders were scheduled, so check the
+            // whole batch before taking any of them out of scheduling
+            for order_id in &due_ids {
+                let order = lock_order(&book.scheduled_orders[order_id])?;
+                book.validate_replacement(&order, Some(*order_id))?;
+            }
+
+            let mut placed = false;
+            for order_id in due_ids {
+                if let Some(order) = book.scheduled_orders.remove(&order_id) {
+                    placed |= book.insert_order(&order)?;
+                }
+            }
+            if !placed || book.phase != Phase::Continuous {
+                return Ok(None);
+            }
+            book.match_orders()
+        })
+    }
+
+    /// Cancels every GoodForDay order in the book, leaving other orders resting.
+    /// Returns the ids of the cancelled orders.
+    pub fn end_of_day(&mut self) -> Vec<OrderId> {
+        self.notifying(|book| {
+            let day_order_ids: Vec<OrderId> = book
+                .bid_side
+                .values()
+                .chain(book.ask_side.values())
+                .flat_map(|orders| orders.values())
+                .filter_map(|order| {
+                    let order = read_order(order);
+                    match order.get_order_type() {
+                        OrderType::GoodForDay => Some(*order.get_order_id()),
+                        _ => None,
+                    }
+                })
+                .collect();
+
+            day_order_ids
+                .into_iter()
+                .filter(|order_id| book.cancel_order(*order_id).is_ok())
+                .collect()
+        })
+    }
+
+    /// Cancels every GoodForCount order that has seen more later orders than its count allows
+    /// by `current_seq`, typically [`get_next_seq`](Self::get_next_seq). An order's age is
+    /// counted from the sequence it last joined the back of its level with.
+    /// Returns the ids of the cancelled orders.
+    pub fn expire_by_age(&mut self, current_seq: u64) -> Vec<OrderId> {
+        self.notifying(|book| {
+            let expired_order_ids: Vec<OrderId> = book
+                .bid_side
+                .values()
+                .chain(book.ask_side.values())
+                .flat_map(|orders| orders.values())
+                .filter_map(|order| {
+                    let order = read_order(order);
+                    let OrderType::GoodForCount(count) = *order.get_order_type() else {
+                        return None;
+                    };
+                    let seq = book.track_orders.get(order.get_order_id())?.seq;
+                    (current_seq.saturating_sub(seq) > count).then_some(*order.get_order_id())
+                })
+                .collect();
+
+            expired_order_ids
+                .into_iter()
+                .filter(|order_id| book.cancel_order(*order_id).is_ok())
+                .collect()
+        })
+    }
+
+    /// Cancels every order resting at `price` on `side`, removing the whole level at once.
+    /// Returns the ids of the cancelled orders in time priority order.
+    pub fn cancel_level(&mut self, side: Side, price: Price) -> Vec<OrderId> {
+        self.notifying(|book| {
+            let book_side = match side {
+                Side::Buy => &mut book.bid_side,
+                Side::Sell => &mut book.ask_side,
+            };
+            let Some(orders) = book_side.remove(&price) else {
+                return vec![];
+            };
+            book.level_emptied(side, price);
+
+            let mut order_ids = Vec::with_capacity(orders.len());
+            for (order_id, order) in orders {
+                book.track_orders.remove(&order_id);
+                if book.strict_order_ids {
+                    remember_order_id(&mut book.recently_cancelled, order_id);
+                }
+                let quantity = *read_order(&order).get_remaining_quantity();
+                book.emit(BookEvent::OrderRemoved {
+                    order_id,
+                    side,
+                    price,
+                    quantity,
+                });
+                order_ids.push(order_id);
+            }
+            order_ids
+        })
+    }
+
+    /// Cancels the order at the front of the time queue at `price` on `side`.
+    /// Returns its id, or None if nothing rests there.
+    pub fn cancel_oldest_at(&mut self, side: Side, price: Price) -> Option<OrderId> {
+        let order_id = *self.side_levels(side).get(&price)?.front()?.0;
+        self.cancel_order(order_id).ok()
+    }
+
+    /// Cancels the order at the back of the time queue at `price` on `side`.
+    /// Returns its id, or None if nothing rests there.
+    pub fn cancel_newest_at(&mut self, side: Side, price: Price) -> Option<OrderId> {
+        let order_id = *self.side_levels(side).get(&price)?.back()?.0;
+        self.cancel_order(order_id).ok()
+    }
+
+    /// Cancels every order resting on `side` at a price in `[low, high]`.
+    /// Returns the ids of the cancelled orders, lowest price first and in time priority order
+    /// within a level.
+    pub fn cancel_in_range(&mut self, side: Side, low: Price, high: Price) -> Vec<OrderId> {
+        if low > high {
+            return vec![];
+        }
+        self.notifying(|book| {
+            let prices: Vec<Price> = book
+                .side_levels(side)
+                .range(low..=high)
+                .map(|(price, _)| *price)
+                .collect();
+            prices
+                .into_iter()
+                .flat_map(|price| book.cancel_level(side, price))
+                .collect()
+        })
+    }
+
+    /// Quantity traded by orders ahead of `order_id` in its level since it joined the queue.
+    /// Modifying the order, or a reserve order refreshing, starts a new count.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    pub fn filled_ahead(&self, order_id: OrderId) -> BookResult<Quantity> {
+        self.track_orders
+            .get(&order_id)
+            .map(|order_entry| order_entry.filled_ahead)
+            .ok_or_else(|| self.missing_order_error(order_id))
+    }
+
+    /// How long `order_id` has rested by `now`, counted in sequence numbers like
+    /// [`get_next_seq`](Self::get_next_seq). A reserve order refreshing keeps its age, a
+    /// modify starts a new one.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    pub fn resting_age(&self, order_id: OrderId, now: u64) -> BookResult<u64> {
+        self.track_orders
+            .get(&order_id)
+            .map(|order_entry| now.saturating_sub(order_entry.inserted_seq))
+            .ok_or_else(|| self.missing_order_error(order_id))
+    }
+
+    /// Rough fill probability proxy in `[0, 1)` from the order's place in its level:
+    /// `quantity_ahead / (quantity_ahead + quantity_behind + 1)`, over remaining quantities.
+    /// Near 0 at the front of the queue, approaching 1 at the back of a deep one.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    /// - Returns [`TrackingDesync`](crate::error::OrderBookError)
+    pub fn queue_ahead_ratio(&self, order_id: OrderId) -> BookResult<f64> {
+        let order_entry = self
+            .track_orders
+            .get(&order_id)
+            .ok_or_else(|| self.missing_order_error(order_id))?;
+        let orders = self
+            .side_levels(order_entry.book_side)
+            .get(&order_entry.price)
+            .filter(|orders| orders.contains_key(&order_id))
+            .ok_or(TrackingDesync { order_id })?;
+
+        let (mut quantity_ahead, mut quantity_behind) = (0u64, 0u64);
+        let mut ahead = true;
+        for (resting_id, order) in orders {
+            if *resting_id == order_id {
+                ahead = false;
+                continue;
+            }
+            let quantity = *read_order(order).get_remaining_quantity() as u64;
+            match ahead {
+                true => quantity_ahead += quantity,
+                false => quantity_behind += quantity,
+            }
+        }
+        Ok(quantity_ahead as f64 / (quantity_ahead + quantity_behind + 1) as f64)
+    }
+
+    /// Ids of every order resting in the book, in no particular order.
+    /// Pegged orders waiting for a midpoint are included.
+    pub fn open_order_ids(&self) -> Vec<OrderId> {
+        self.track_orders
+            .keys()
+            .chain(self.parked_pegs.keys())
+            .copied()
+            .collect()
+    }
+
+    /// Ids of every order resting on one side of the book, in no particular order.
+    /// Pegged orders waiting for a midpoint are included.
+    pub fn open_order_ids_side(&self, side: Side) -> Vec<OrderId> {
+        let parked = self
+            .parked_pegs
+            .iter()
+            .filter(|(_, order)| *read_order(order).get_side() == side)
+            .map(|(order_id, _)| *order_id);
+        self.track_orders
+            .values()
+            .filter(|order_entry| order_entry.book_side == side)
+            .map(|order_entry| order_entry.order_id)
+            .chain(parked)
+            .collect()
+    }
+
+    pub fn get_order_infos(&self) -> OrderBookLevelInfos {
+        // grab price, quantity
+        // for every price level, sum up all order quantities
+        OrderBookLevelInfos {
+            bids: self.displayed_level_infos(Side::Buy),
+            asks: self.displayed_level_infos(Side::Sell),
+        }
+    }
+
+    /// Takes a snapshot of the book's current levels that is cheap to share between readers.
+    pub fn snapshot(&self) -> Arc<BookSnapshot> {
+        Arc::new(BookSnapshot {
+            asset: self.asset,
+            level_infos: self.get_order_infos(),
+        })
+    }
+
+    /// Aggregated quantity for the best `levels` price levels of each side.
+    /// Unlike [`get_order_infos`](Self::get_order_infos), both sides are ordered best price first.
+    pub fn depth(&self, levels: usize) -> OrderBookLevelInfos {
+        OrderBookLevelInfos {
+            bids: self.level_infos(self.bid_side.iter().rev().take(levels)),
+            asks: self.level_infos(self.ask_side.iter().take(levels)),
+        }
+    }
+
+    /// CRC32 of the best `levels` levels of each side, for feed consumers to check a rebuilt
+    /// book against. Computed over `price:quantity` pairs, best price first, joined by `,`,
+    /// with the bids then the asks joined by `|`, e.g. `100:40,99:10|101:30`.
+    pub fn checksum(&self, levels: usize) -> u32 {
+        let depth = self.depth(levels);
+        let side = |infos: &LevelInfos| -> String {
+            infos
+                .iter()
+                .map(|level| format!("{}:{}", level.price, level.quantity))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let canonical = format!("{}|{}", side(depth.get_bids()), side(depth.get_asks()));
+        crc32fast::hash(canonical.as_bytes())
+    }
+
+    /// Renders the best `levels` levels of each side as a price ladder, asks above bids and
+    /// prices descending, e.g.
+    ///
+    /// ```text
+    /// ASK   101.00       30 ##############################
+    /// BID   100.00       40 ########################################
+    /// ```
+    ///
+    /// Bars scale with each level's quantity relative to the largest level shown.
+    pub fn to_ascii_heatmap(&self, levels: usize) -> String {
+        let depth = self.depth(levels);
+        let largest = depth
+            .get_bids()
+            .iter()
+            .chain(depth.get_asks())
+            .map(|level| level.quantity)
+            .max()
+            .unwrap_or(0);
+
+        let mut heatmap = String::new();
+        let rows = depth
+            .get_asks()
+            .iter()
+            .rev()
+            .map(|level| ("ASK", level))
+            .chain(depth.get_bids().iter().map(|level| ("BID", level)));
+        for (label, level) in rows {
+            // every non-empty level gets at least one character
+            let bar_width = (level.quantity as u64 * HEATMAP_BAR_WIDTH)
+                .div_ceil(largest as u64)
+                .max(1);
+            let _ = writeln!(
+                heatmap,
+                "{label} {:>8} {:>8} {}",
+                price_to_decimal_string(level.price),
+                level.quantity,
+                "#".repeat(bar_width as usize)
+            );
+        }
+        heatmap
+    }
+
+    /// `(price, quantity)` of every bid level, highest price first.
+    pub fn bid_ladder(&self) -> Vec<(Price, Quantity)> {
+        self.bid_side
+            .iter()
+            .rev()
+            .map(|(price, orders)| (*price, self.level_quantity(orders)))
+            .collect()
+    }
+
+    /// `(price, quantity)` of every ask level, lowest price first.
+    pub fn ask_ladder(&self) -> Vec<(Price, Quantity)> {
+        self.ask_side
+            .iter()
+            .map(|(price, orders)| (*price, self.level_quantity(orders)))
+            .collect()
+    }
+
+    /// `(price, quantity)` of every level on `side`, best price first, with each quantity the
+    /// running total from the best price out to that level, saturating at [`Quantity::MAX`].
+    pub fn cumulative_depth(&self, side: Side) -> Vec<(Price, Quantity)> {
+        let ladder = match side {
+            Side::Buy => self.bid_ladder(),
+            Side::Sell => self.ask_ladder(),
+        };
+        ladder
+            .into_iter()
+            .scan(0 as Quantity, |total, (price, quantity)| {
+                *total = total.saturating_add(quantity);
+                Some((price, *total))
+            })
+            .collect()
+    }
+
+    /// Inverse of [`cumulative_depth`](Self::cumulative_depth): the worst price on `side` an
+    /// aggressor taking `target_quantity` from it would reach. Returns None if `side` doesn't
+    /// hold that much quantity.
+    pub fn price_at_depth(&self, side: Side, target_quantity: Quantity) -> Option<Price> {
+        self.cumulative_depth(side)
+            .into_iter()
+            .find(|(_, quantity)| *quantity >= target_quantity)
+            .map(|(price, _)| price)
+    }
+
+    /// Number of occupied price levels on `side`.
+    pub fn level_count(&self, side: Side) -> usize {
+        self.side_levels(side).len()
+    }
+
+    /// Highest bid price resting in the book.
+    pub fn best_bid(&self) -> Option<Price> {
+        self.best_price(Side::Buy)
+    }
+
+    /// Lowest ask price resting in the book.
+    pub fn best_ask(&self) -> Option<Price> {
+        self.best_price(Side::Sell)
+    }
+
+    /// Best price resting on a side of the book.
+    fn best_price(&self, side: Side) -> Option<Price> {
+        let levels = self.side_levels(side);
+        let (lowest, highest) = (*levels.keys().next()?, *levels.keys().next_back()?);
+        match is_better_price(side, highest, lowest) {
+            true => Some(highest),
+            false => Some(lowest),
+        }
+    }
+
+    /// Difference between the best ask and the best bid, negative if the book is crossed.
+    /// Returns None if either side is empty.
+    pub fn spread(&self) -> Option<i64> {
+        Some(self.best_ask()? as i64 - self.best_bid()? as i64)
+    }
+
+    /// Whether the best bid equals the best ask without the two having matched, which can
+    /// happen while matching is paused. False if either side is empty.
+    pub fn is_locked(&self) -> bool {
+        matches!(
+            (self.best_bid(), self.best_ask()),
+            (Some(best_bid), Some(best_ask)) if best_bid == best_ask
+        )
+    }
+
+    /// Midpoint of the best bid and best ask. Returns None if either side is empty.
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best_bid()? as f64 + self.best_ask()? as f64) / 2.0)
+    }
+
+    /// [`spread`](Self::spread) relative to [`mid_price`](Self::mid_price), in basis points.
+    /// Returns None if either side is empty or the mid is zero.
+    pub fn spread_bps(&self) -> Option<f64> {
+        let mid_price = self.mid_price()?;
+        if mid_price == 0.0 {
+            return None;
+        }
+        Some(self.spread()? as f64 / mid_price * 10_000.0)
+    }
+
+    /// Bid and ask quantity resting within `bps` basis points of the mid.
+    /// Returns None if either side is empty.
+    pub fn liquidity_within(&self, bps: f64) -> Option<(Quantity, Quantity)> {
+        let mid_price = self.mid_price()?;
+        let window = mid_price.abs() * bps / 10_000.0;
+        // float to int casts saturate, so windows past the price range are fine
+        let low = (mid_price - window).ceil() as Price;
+        let high = (mid_price + window).floor() as Price;
+        Some((
+            self.quantity_in_range(Side::Buy, low, high),
+            self.quantity_in_range(Side::Sell, low, high),
+        ))
+    }
+
+    /// Effective spread of an aggressor on `side` taking `quantity`: twice the distance between
+    /// the sweep's average fill price and the mid. Returns None if either side is empty or the
+    /// book can't fill the whole quantity.
+    pub fn effective_spread(&self, side: Side, quantity: Quantity) -> Option<f64> {
+        let mid_price = self.mid_price()?;
+        let sweep = self.simulate_sweep(side, quantity);
+        if *sweep.get_unfilled_quantity() > 0 {
+            return None;
+        }
+        Some(2.0 * (sweep.vwap()? - mid_price).abs())
+    }
+
+    /// Cost, in cents, of buying `quantity` and immediately selling it back by sweeping the book:
+    /// the difference between the buy and sell sweeps' average prices, times `quantity`.
+    /// Returns None if either sweep can't be filled in full.
+    pub fn round_trip_cost(&self, quantity: Quantity) -> Option<f64> {
+        let vwap = |side| {
+            let sweep = self.simulate_sweep(side, quantity);
+            match sweep.get_unfilled_quantity() {
+                0 => sweep.vwap(),
+                _ => None,
+            }
+        };
+        Some((vwap(Side::Buy)? - vwap(Side::Sell)?) * quantity as f64)
+    }
+
+    /// Midpoint weighted by the opposite side's top of book quantity, so it leans toward the
+    /// side with less resting interest. Returns None if either side is empty.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid_price, bids) = self.bid_side.iter().next_back()?;
+        let (ask_price, asks) = self.ask_side.iter().next()?;
+
+        let bid_quantity = self.level_quantity(bids) as f64;
+        let ask_quantity = self.level_quantity(asks) as f64;
+        if bid_quantity + ask_quantity == 0.0 {
+            return None;
+        }
+
+        Some(
+            (*bid_price as f64 * ask_quantity + *ask_price as f64 * bid_quantity)
+                / (bid_quantity + ask_quantity),
+        )
+    }
+
+    /// Midpoint of each side's quantity weighted average price over its best `levels` levels,
+    /// a smoother fair value than [`microprice`](Self::microprice).
+    /// Returns None if either side has no quantity within those levels.
+    pub fn depth_weighted_mid(&self, levels: usize) -> Option<f64> {
+        let depth = self.depth(levels);
+        let weighted_price = |infos: &LevelInfos| -> Option<f64> {
+            let (notional, quantity) =
+                infos
+                    .iter()
+                    .fold((0.0, 0.0), |(notional, quantity), level| {
+                        (
+                            notional + level.price as f64 * level.quantity as f64,
+                            quantity + level.quantity as f64,
+                        )
+                    });
+            (quantity > 0.0).then(|| notional / quantity)
+        };
+        Some((weighted_price(depth.get_bids())? + weighted_price(depth.get_asks())?) / 2.0)
+    }
+
+    /// Quantity that has to be taken from `side` of the book for its best price to reach
+    /// `target`, i.e. everything resting at a better price than `target`.
+    /// Asks below `target` for [`Side::Sell`], bids above it for [`Side::Buy`].
+    pub fn quantity_to_price(&self, side: Side, target: Price) -> Quantity {
+        let levels = match side {
+            Side::Buy => self.bid_side.range((Excluded(target), Unbounded)),
+            Side::Sell => self.ask_side.range(..target),
+        };
+        levels.map(|(_, orders)| self.level_quantity(orders)).sum()
+    }
+
+    /// Order book imbalance over the top `levels` levels, in `[-1, 1]`.
+    /// Positive when bids outweigh asks. Returns None if either side is empty.
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let depth = self.depth(levels);
+        if depth.get_bids().is_empty() || depth.get_asks().is_empty() {
+            return None;
+        }
+
+        let total =
+            |infos: &LevelInfos| -> f64 { infos.iter().map(|level| level.quantity as f64).sum() };
+        let bid_quantity = total(depth.get_bids());
+        let ask_quantity = total(depth.get_asks());
+
+        Some((bid_quantity - ask_quantity) / (bid_quantity + ask_quantity))
+    }
+
+    /// Total value, in cents, of the remaining quantity of every order resting on `side`.
+    pub fn gross_notional(&self, side: Side) -> i128 {
+        self.side_levels(side)
+            .iter()
+            .flat_map(|(price, orders)| {
+                orders.iter().map(|(order_id, order)| {
+                    *price as i128 * self.resting_quantity(order_id, order) as i128
+                })
+            })
+            .sum()
+    }
+
+    /// Quantity weighted average price of everything resting on `side`.
+    /// Returns None if nothing rests on that side.
+    pub fn resting_vwap(&self, side: Side) -> Option<f64> {
+        let mut quantity: u64 = 0;
+        let mut notional: i128 = 0;
+        for (price, orders) in self.side_levels(side) {
+            let level_quantity = self.level_quantity(orders) as u64;
+            quantity += level_quantity;
+            notional += *price as i128 * level_quantity as i128;
+        }
+
+        match quantity {
+            0 => None,
+            _ => Some(notional as f64 / quantity as f64),
+        }
+    }
+
+    /// Remaining quantity of each order at a price level, in time priority order.
+    /// Returns None if nothing rests at that price.
+    pub fn level_orders(&self, side: Side, price: Price) -> Option<Vec<(OrderId, Quantity)>> {
+        let orders = self.side_levels(side).get(&price)?;
+        Some(
+            orders
+                .iter()
+                .map(|(order_id, order)| (*order_id, *read_order(order).get_remaining_quantity()))
+                .collect(),
+        )
+    }
+
+    /// Sums remaining quantity on a side across levels priced within `[low, high]`.
+    /// Only the levels inside the band are visited.
+    pub fn quantity_in_range(&self, side: Side, low: Price, high: Price) -> Quantity {
+        if low > high {
+            return 0;
+        }
+        self.side_levels(side)
+            .range(low..=high)
+            .map(|(_, orders)| self.level_quantity(orders))
+            .sum()
+    }
+
+    /// Simulates an aggressor on `side` taking `quantity` from the opposite side, without
+    /// touching the book. Levels are consumed best price first, as `match_orders` would.
+    pub fn simulate_sweep(&self, side: Side, quantity: Quantity) -> SweepResult {
+        let mut fills = vec![];
+        let mut unfilled_quantity = quantity;
+
+        for (price, orders) in self.opposite_levels(side) {
+            if unfilled_quantity == 0 {
+                break;
+            }
+            let fill_quantity = min(unfilled_quantity, self.level_quantity(orders));
+            fills.push((*price, fill_quantity));
+            unfilled_quantity -= fill_quantity;
+        }
+
+        SweepResult {
+            fills,
+            filled_quantity: quantity - unfilled_quantity,
+            unfilled_quantity,
+        }
+    }
+
+    /// Number of distinct resting orders an aggressor on `side` taking `quantity` would trade
+    /// with, fully or partially, without touching the book. Like
+    /// [`simulate_sweep`](Self::simulate_sweep), only visible quantity is counted.
+    pub fn orders_touched(&self, side: Side, quantity: Quantity) -> usize {
+        let mut unfilled_quantity = quantity;
+        let mut touched = 0;
+        for (_, orders) in self.opposite_levels(side) {
+            let mut quantities: Vec<Quantity> = orders
+                .iter()
+                .map(|(order_id, order)| self.resting_quantity(order_id, order))
+                .filter(|quantity| *quantity > 0)
+                .collect();
+            if self.level_ordering == LevelOrdering::SizeThenTime {
+                // stable, so the earlier order still goes first on ties
+                quantities.sort_by(|a, b| b.cmp(a));
+            }
+            for quantity in quantities {
+                if unfilled_quantity == 0 {
+                    return touched;
+                }
+                touched += 1;
+                unfilled_quantity = unfilled_quantity.saturating_sub(quantity);
+            }
+        }
+        touched
+    }
+
+    /// Splits `quantity` across the orders resting on `side` at `price` in proportion to their
+    /// visible quantity, handing out the residual by the book's [`Rounding`], without touching
+    /// the book. Returns each order's allocation in time priority, and allocations always sum to
+    /// the smaller of `quantity` and the level's visible quantity. Matching itself still fills
+    /// one order at a time.
+    pub fn pro_rata_allocation(
+        &self,
+        side: Side,
+        price: Price,
+        quantity: Quantity,
+    ) -> Vec<(OrderId, Quantity)> {
+        let Some(orders) = self.side_levels(side).get(&price) else {
+            return vec![];
+        };
+        let (order_ids, sizes): (Vec<OrderId>, Vec<Quantity>) = orders
+            .iter()
+            .map(|(order_id, order)| (*order_id, self.resting_quantity(order_id, order)))
+            .unzip();
+        order_ids
+            .into_iter()
+            .zip(pro_rata(quantity, &sizes, self.rounding))
+            .collect()
+    }
+
+    /// Total price improvement an aggressor on `side` with limit `price` would get taking up to
+    /// `quantity`: how much better than its limit each unit fills, summed over the fill.
+    /// Returns None if nothing would fill within the limit, or the total doesn't fit a [`Price`].
+    pub fn price_improvement(&self, side: Side, price: Price, quantity: Quantity) -> Option<Price> {
+        let mut unfilled_quantity = quantity;
+        // a spread near the full Price range times a large fill overflows an i64
+        let mut improvement: i128 = 0;
+
+        for (level_price, orders) in self.opposite_levels(side) {
+            if unfilled_quantity == 0 || is_better_price(side, *level_price, price) {
+                break;
+            }
+            let fill_quantity = min(unfilled_quantity, self.level_quantity(orders));
+            improvement += (price as i128 - *level_price as i128).abs() * fill_quantity as i128;
+            unfilled_quantity -= fill_quantity;
+        }
+
+        if unfilled_quantity == quantity {
+            return None;
+        }
+        Price::try_from(improvement).ok()
+    }
+
+    /// Quantity of `order` that would be left to rest after it took everything available at
+    /// prices within its limit, without touching the book.
+    pub fn residual_after(&self, order: &Order) -> Quantity {
+        let (side, limit) = (*order.get_side(), *order.get_price());
+        let available: u64 = self
+            .opposite_levels(side)
+            .take_while(|(price, _)| !is_better_price(side, **price, limit))
+            .map(|(_, orders)| self.level_quantity(orders) as u64)
+            .sum();
+        order
+            .get_remaining_quantity()
+            .saturating_sub(available.min(Quantity::MAX as u64) as Quantity)
+    }
+
+    /// Every order resting on `side` at a price in `[low, high]`, lowest price first and in time
+    /// priority order within a level.
+    pub fn orders_in_range(&self, side: Side, low: Price, high: Price) -> Vec<OrderRef> {
+        if low > high {
+            return vec![];
+        }
+        self.side_levels(side)
+            .range(low..=high)
+            .flat_map(|(_, orders)| orders.values().cloned())
+            .collect()
+    }
+
+    /// Resting order an incoming aggressor on `side` would hit first, if any.
+    pub fn next_match(&self, side: Side) -> Option<OrderRef> {
+        let (_, orders) = self.opposite_levels(side).next()?;
+        let order_id = next_in_level(orders, &mut self.level_queue(orders))?;
+        orders.get(&order_id).cloned()
+    }
+
+    /// Checks whether order can be matched given book's current state.
+    fn can_match(&self, side: &Side, price: &Price) -> bool {
+        // matchable unless the best opposing price is beyond the order's limit
+        self.best_price(side.opposite()) // None if opposite side empty
+            .is_some_and(|best_opposite_price| !is_better_price(*side, best_opposite_price, *price))
+    }
+
+    /// Match bids and asks.
+    /// Returns None if no matches are currently possible.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    fn match_orders(&mut self) -> BookResult<Option<Trades>> {
+        let mut trades: Trades = vec![];
+        self.match_orders_into(&mut trades)?;
+        Ok((!trades.is_empty()).then_some(trades))
+    }
+
+    /// Match bids and asks like a regular match pass, also returning the id of the resting
+    /// order consumed by each trade, in matching order. Meant for asserting priority in tests,
+    /// e.g. by queueing orders during [`Phase::Auction`] and matching them here.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    #[cfg(debug_assertions)]
+    pub fn match_orders_traced(&mut self) -> BookResult<(Trades, Vec<OrderId>)> {
+        let mut trades: Trades = vec![];
+        self.match_trace = Some(vec![]);
+        let result = self.match_orders_into(&mut trades);
+        let match_trace = self.match_trace.take().unwrap_or_default();
+        result?;
+        Ok((trades, match_trace))
+    }
+
+    /// Matches bids and asks like [`match_orders`](Self::match_orders), then cancels whatever
+    /// FillAndKill orders placed during the auction or halt left unfilled, reached by the pass
+    /// or not.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    fn uncross_held(&mut self) -> BookResult<Option<Trades>> {
+        let matched = self.match_orders();
+        let held_faks = mem::take(&mut self.held_faks);
+        let killed = self.kill_unfilled_faks(held_faks);
+        let trades = matched?;
+        killed?;
+        Ok(trades)
+    }
+
+    /// Cancels each of `order_ids` still resting as a FillAndKill order.
+    ///
+    /// # Errors:
+    /// - Returns [`TrackingDesync`](crate::error::OrderBookError)
+    fn kill_unfilled_faks(&mut self, order_ids: Vec<OrderId>) -> BookResult<()> {
+        for order_id in order_ids {
+            // filled, cancelled, or the id now belongs to another order
+            let is_fak = self.get_order_ref(&order_id).is_ok_and(|order| {
+                matches!(read_order(order).get_order_type(), OrderType::FillAndKill)
+            });
+            if is_fak {
+                self.cancel_order(order_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Match bids and asks, appending the resulting trades to `trades`.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    fn match_orders_into(&mut self, trades: &mut Trades) -> BookResult<()> {
+        // resting orders the pre-trade hook vetoed, held out of their levels for this pass
+        let mut vetoed = vec![];
+        let result = self.match_levels(trades, &mut vetoed);
+        self.restore_vetoed(vetoed);
+        result
+    }
+
+    /// Matches the best bid and ask levels until they no longer cross, or until the pass makes
+    /// `max_fills_per_match` fills. Either way the sweep of each FillAndKill order the pass
+    /// matched is over, so whatever it left unfilled is cancelled before returning.
+    /// Resting orders the pre-trade hook vetoes are taken out of their level into `vetoed`.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    fn match_levels(&mut self, trades: &mut Trades, vetoed: &mut Vec<OrderRef>) -> BookResult<()> {
+        let mut fills = 0;
+        // FillAndKill orders this pass matched, in the order it reached them
+        let mut swept_faks: Vec<OrderId> = vec![];
+        // loops as long as there are orders to match
+        'matching: loop {
+            // if either bids or asks empty, no matches possible
+            if self.ask_side.is_empty() || self.bid_side.is_empty() {
+                break;
+            }
+
+            let (best_bid_price, mut bids) = match self.bid_side.pop_last() {
+                Some((b_b_p, bids)) => (b_b_p, bids),
+                None => break,
+            };
+
+            // self.ask_side.pop_first().map_or(break, |(bap, asks)| (bap, asks));
+            let (best_ask_price, mut asks) = match self.ask_side.pop_first() {
+                Some((b_a_p, asks)) => (b_a_p, asks),
+                None => break,
+            };
+
+            if is_better_price(Side::Buy, best_ask_price, best_bid_price) {
+                // no matches possible, put the levels back untouched
+                self.bid_side.insert(best_bid_price, bids);
+                self.ask_side.insert(best_ask_price, asks);
+                break;
+            }
+
+            // order the sweep consumes each level in, unless that's simply time priority
+            let mut bid_queue = self.level_queue(&bids);
+            let mut ask_queue = self.level_queue(&asks);
+
+            // match best bids with best asks
+            while !bids.is_empty() && !asks.is_empty() {
+                if self.max_fills_per_match.is_some_and(|max| fills >= max) {
+                    self.bid_side.insert(best_bid_price, bids);
+                    self.ask_side.insert(best_ask_price, asks);
+                    return self.kill_fak_remainders(swept_faks, vetoed);
+                }
+
+                let (bid_id, ask_id) = match (
+                    next_in_level(&bids, &mut bid_queue),
+                    next_in_level(&asks, &mut ask_queue),
+                ) {
+                    (Some(bid_id), Some(ask_id)) => (bid_id, ask_id),
+                    _ => break, // unreachable
+                };
+
+                let poisoned = [&bids[&bid_id], &asks[&ask_id]]
+                    .into_iter()
+                    .find_map(|order| lock_order(order).err());
+                if let Some(err) = poisoned {
+                    // leave both levels in the book, the poisoned order can still be cancelled
+                    self.bid_side.insert(best_bid_price, bids);
+                    self.ask_side.insert(best_ask_price, asks);
+                    self.kill_fak_remainders(swept_faks, vetoed)?;
+                    return Err(err);
+                }
+
+                let mut bid = read_order(&bids[&bid_id]);
+                let mut ask = read_order(&asks[&ask_id]);
+                for order in [&bid, &ask] {
+                    let order_id = *order.get_order_id();
+                    if matches!(order.get_order_type(), OrderType::FillAndKill)
+                        && !swept_faks.contains(&order_id)
+                    {
+                        swept_faks.push(order_id);
+                    }
+                }
+
+                // same owner on both sides, cancel instead of trading with itself
+                let self_match_ids = self.self_match_cancels(&bid, &ask);
+                if !self_match_ids.is_empty() {
+                    mem::drop(bid);
+                    mem::drop(ask);
+                    self.bid_side.insert(best_bid_price, bids);
+                    self.ask_side.insert(best_ask_price, asks);
+                    for order_id in self_match_ids {
+                        self.cancel_order(order_id)?;
+                    }
+                    continue 'matching;
+                }
+
+                // reduce only orders can't grow their owner's position, so any part that would
+                // is cancelled, and the whole order once the position is flat
+                let closed_out_ids: Vec<OrderId> = [
+                    self.trim_reduce_only(&mut bid),
+                    self.trim_reduce_only(&mut ask),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                if !closed_out_ids.is_empty() {
+                    mem::drop(bid);
+                    mem::drop(ask);
+                    self.bid_side.insert(best_bid_price, bids);
+                    self.ask_side.insert(best_ask_price, asks);
+                    for order_id in closed_out_ids {
+                        self.cancel_order(order_id)?;
+                    }
+                    continue 'matching;
+                }
+
+                // an order with nothing left to fill can't trade, it gets dropped below
+                // instead of producing an empty trade
+                if !bid.is_filled() && !ask.is_filled() {
+                    let fill_quantity =
+                        min(*bid.get_remaining_quantity(), *ask.get_remaining_quantity());
+
+                    // the pre-trade hook can veto the pair, the resting order then sits out
+                    // the rest of this pass and the aggressor moves on to the next one
+                    let bid_is_resting =
+                        self.resting_order(&bid, &ask).get_order_id() == bid.get_order_id();
+                    if let Some(hook) = self.pre_trade_hook.as_mut() {
+                        let (aggressor, resting) = match bid_is_resting {
+                            true => (&*ask, &*bid),
+                            false => (&*bid, &*ask),
+                        };
+                        if !hook(aggressor, resting, fill_quantity) {
+                            mem::drop(bid);
+                            mem::drop(ask);
+                            let removed = match bid_is_resting {
+                                true => bids.remove(&bid_id),
+                                false => asks.remove(&ask_id),
+                            };
+                            vetoed.extend(removed);
+                            continue;
+                        }
+                    }
+
+                    bid.fill(fill_quantity)?;
+                    ask.fill(fill_quantity)?;
+                    self.mirror_remaining_quantity(&bid);
+                    self.mirror_remaining_quantity(&ask);
+                    self.credit_filled_ahead(&bids, bid_id, fill_quantity);
+                    self.credit_filled_ahead(&asks, ask_id, fill_quantity);
+
+                    let (aggressor, resting) = match bid_is_resting {
+                        true => (&*ask, &*bid),
+                        false => (&*bid, &*ask),
+                    };
+                    let execution_price = self.execution_price_of(aggressor, resting);
+                    #[cfg(debug_assertions)]
+                    if let Some(match_trace) = self.match_trace.as_mut() {
+                        match_trace.push(*resting.get_order_id());
+                    }
+
+                    let trade = Trade::new(
+                        TradeInfo {
+                            order_id: *bid.get_order_id(),
+                            price: *bid.get_price(),
+                            quantity: fill_quantity,
+                            owner: *bid.get_owner(),
+                            tag: bid.get_tag().clone(),
+                        },
+                        TradeInfo {
+                            order_id: *ask.get_order_id(),
+                            price: *ask.get_price(),
+                            quantity: fill_quantity,
+                            owner: *ask.get_owner(),
+                            tag: ask.get_tag().clone(),
+                        },
+                        execution_price,
+                    );
+
+                    self.total_matched_quantity += fill_quantity as u64;
+                    self.total_matched_notional += trade.get_notional();
+                    *self.positions.entry(*bid.get_owner()).or_default() += fill_quantity as i64;
+                    *self.positions.entry(*ask.get_owner()).or_default() -= fill_quantity as i64;
+
+                    println!("{:?}", trade);
+
+                    for order in [&bid, &ask] {
+                        self.emit(BookEvent::OrderFilled {
+                            order_id: *order.get_order_id(),
+                            side: *order.get_side(),
+                            price: *order.get_price(),
+                            quantity: fill_quantity,
+                        });
+                    }
+
+                    if let Some(listener) = self.trade_listener.as_mut() {
+                        listener(trade.view());
+                    }
+
+                    for trade_info in [trade.get_bid_trade(), trade.get_ask_trade()] {
+                        self.trade_indices
+                            .entry(trade_info.order_id)
+                            .or_default()
+                            .push(self.trade_history.len());
+                    }
+                    self.trade_history.push(trade.clone());
+                    trades.push(trade);
+                    fills += 1;
+                }
+
+                if bid.is_filled() {
+                    let refreshed = bid.refresh_from_reserve();
+                    if refreshed {
+                        self.mirror_remaining_quantity(&bid);
+                        self.emit(BookEvent::OrderRefreshed {
+                            order_id: *bid.get_order_id(),
+                            side: *bid.get_side(),
+                            price: *bid.get_price(),
+                            quantity: *bid.get_remaining_quantity(),
+                        });
+                    }
+                    mem::drop(bid);
+                    if refreshed {
+                        self.requeue_order(&mut bids, bid_id);
+                        requeue_in_queue(&mut bid_queue, bid_id);
+                    } else {
+                        bids.remove(&bid_id);
+                        self.forget_filled_order(bid_id);
+                    }
+                }
+
+                if ask.is_filled() {
+                    let refreshed = ask.refresh_from_reserve();
+                    if refreshed {
+                        self.mirror_remaining_quantity(&ask);
+                        self.emit(BookEvent::OrderRefreshed {
+                            order_id: *ask.get_order_id(),
+                            side: *ask.get_side(),
+                            price: *ask.get_price(),
+                            quantity: *ask.get_remaining_quantity(),
+                        });
+                    }
+                    mem::drop(ask);
+                    if refreshed {
+                        self.requeue_order(&mut asks, ask_id);
+                        requeue_in_queue(&mut ask_queue, ask_id);
+                    } else {
+                        asks.remove(&ask_id);
+                        self.forget_filled_order(ask_id);
+                    }
+                }
+            }
+
+            if bids.is_empty() {
+                self.level_emptied(Side::Buy, best_bid_price);
+            } else {
+                self.bid_side.insert(best_bid_price, bids);
+            }
+
+            if asks.is_empty() {
+                self.level_emptied(Side::Sell, best_ask_price);
+            } else {
+                self.ask_side.insert(best_ask_price, asks);
+            }
+        }
+        self.kill_fak_remainders(swept_faks, vetoed)
+    }
+
+    /// Cancels whatever the FillAndKill orders in `swept_faks` left unfilled, once their sweep is
+    /// over. Vetoed ones are put back in their level first, so they're cancelled from there.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    fn kill_fak_remainders(
+        &mut self,
+        swept_faks: Vec<OrderId>,
+        vetoed: &mut Vec<OrderRef>,
+    ) -> BookResult<()> {
+        let (vetoed_faks, still_vetoed) = mem::take(vetoed)
+            .into_iter()
+            .partition(|order| swept_faks.contains(read_order(order).get_order_id()));
+        *vetoed = still_vetoed;
+        self.restore_vetoed(vetoed_faks);
+
+        for order_id in swept_faks {
+            // filled, or already cancelled by self-match prevention
+            if self.track_orders.contains_key(&order_id) {
+                self.cancel_order(order_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts orders vetoed during a match pass back in their levels, in time priority order.
+    /// A vetoed pair stays crossed, so the book can be left crossed until one of them leaves.
+    fn restore_vetoed(&mut self, vetoed: Vec<OrderRef>) {
+        for order in vetoed {
+            let (order_id, side, price) = {
+                let order = read_order(&order);
+                (*order.get_order_id(), *order.get_side(), *order.get_price())
+            };
+            let book_side = match side {
+                Side::Buy => &mut self.bid_side,
+                Side::Sell => &mut self.ask_side,
+            };
+            let mut level: Vec<(OrderId, OrderRef)> = book_side
+                .remove(&price)
+                .unwrap_or_default()
+                .into_iter()
+                .chain([(order_id, order)])
+                .collect();
+            level.sort_by_key(|(order_id, _)| {
+                self.track_orders
+                    .get(order_id)
+                    .map_or(u64::MAX, |order_entry| order_entry.seq)
+            });
+            let book_side = match side {
+                Side::Buy => &mut self.bid_side,
+                Side::Sell => &mut self.ask_side,
+            };
+            book_side.insert(price, level.into_iter().collect());
+        }
+    }
+
+    /// Cuts a reduce only order down to what its owner's position lets it trade.
+    /// Returns its id if it can't trade at all, for the caller to cancel.
+    fn trim_reduce_only(&mut self, order: &mut Order) -> Option<OrderId> {
+        if !order.is_reduce_only() || order.is_filled() {
+            return None;
+        }
+        let position = self.position(*order.get_owner());
+        let reducible = match order.get_side() {
+            Side::Buy => -position,
+            Side::Sell => position,
+        };
+        let capacity = reducible.clamp(0, Quantity::MAX as i64) as Quantity;
+        if capacity == 0 {
+            return Some(*order.get_order_id());
+        }
+
+        let unfilled_quantity = order.get_remaining_quantity() + order.get_hidden_quantity();
+        if capacity < unfilled_quantity {
+            let visible_quantity = *order.get_remaining_quantity();
+            order.reduce_to(order.get_filled_quantity() + capacity);
+            self.mirror_remaining_quantity(order);
+            let removed_quantity = visible_quantity - order.get_remaining_quantity();
+            if removed_quantity > 0 {
+                self.emit(BookEvent::OrderRemoved {
+                    order_id: *order.get_order_id(),
+                    side: *order.get_side(),
+                    price: *order.get_price(),
+                    quantity: removed_quantity,
+                });
+            }
+        }
+        None
+    }
+
+    /// Orders to cancel, under the book's [`StpMode`], rather than let `bid` and `ask` trade.
+    /// Empty unless both have quantity left and share an owner.
+    fn self_match_cancels(&self, bid: &Order, ask: &Order) -> Vec<OrderId> {
+        if self.stp_mode == StpMode::None
+            || bid.get_owner() != ask.get_owner()
+            || *bid.get_owner() == 0
+            || bid.is_filled()
+            || ask.is_filled()
+        {
+            return vec![];
+        }
+
+        let resting_id = *self.resting_order(bid, ask).get_order_id();
+        let newest_id = match resting_id == *bid.get_order_id() {
+            true => *ask.get_order_id(),
+            false => *bid.get_order_id(),
+        };
+        match self.stp_mode {
+            StpMode::None => vec![],
+            StpMode::CancelResting => vec![resting_id],
+            StpMode::CancelNewest => vec![newest_id],
+            StpMode::CancelBoth => vec![resting_id, newest_id],
+        }
+    }
+
+    /// Price `aggressor` and `resting` trade at under the book's [`ExecutionPrice`].
+    fn execution_price_of(&self, aggressor: &Order, resting: &Order) -> Price {
+        let midpoint = match self.pre_mutation_top_of_book {
+            (Some(best_bid), Some(best_ask)) => {
+                midpoint_on_tick(best_bid, best_ask, self.tick_size, *resting.get_side())
+            }
+            _ => return *resting.get_price(),
+        };
+        let (bid, ask) = match aggressor.get_side() {
+            Side::Buy => (aggressor, resting),
+            Side::Sell => (resting, aggressor),
+        };
+        match self.execution_price {
+            ExecutionPrice::RestingPrice => *resting.get_price(),
+            // the midpoint of a crossed book can be beyond either order's limit
+            ExecutionPrice::Midpoint | ExecutionPrice::MidpointCappedByLimit => {
+                midpoint.min(*bid.get_price()).max(*ask.get_price())
+            }
+        }
+    }
+
+    /// Whichever of a matched bid and ask rested in the book first. Its price is the execution
+    /// price under [`ExecutionPrice::RestingPrice`].
+    fn resting_order<'a>(&self, bid: &'a Order, ask: &'a Order) -> &'a Order {
+        let seq = |order: &Order| {
+            self.track_orders
+                .get(order.get_order_id())
+                .map(|order_entry| order_entry.seq)
+        };
+        match seq(bid) <= seq(ask) {
+            true => bid,
+            false => ask,
+        }
+    }
+
+    /// Order the matcher consumes a price level's orders in, ranked once for a whole sweep of the
+    /// level. None under time priority, where the level's own order is the queue.
+    fn level_queue(&self, orders: &OrderRefs) -> Option<VecDeque<OrderId>> {
+        match self.level_ordering {
+            LevelOrdering::Time => None,
+            LevelOrdering::SizeThenTime => {
+                let mut sizes: Vec<(OrderId, Quantity)> = orders
+                    .iter()
+                    .map(|(order_id, order)| {
+                        (*order_id, *read_order(order).get_remaining_quantity())
+                    })
+                    .collect();
+                // stable, so the earlier order still goes first on ties
+                sizes.sort_by(|(_, a), (_, b)| b.cmp(a));
+                Some(sizes.into_iter().map(|(order_id, _)| order_id).collect())
+            }
+        }
+    }
+
+    /// Price levels making up one side of the book.
+    fn side_levels(&self, side: Side) -> &BTreeMap<Price, OrderRefs> {
+        match side {
+            Side::Buy => &self.bid_side,
+            Side::Sell => &self.ask_side,
+        }
+    }
+
+    /// Levels an aggressor on `side` would match against, best price first.
+    fn opposite_levels(&self, side: Side) -> Box<dyn Iterator<Item = (&Price, &OrderRefs)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.ask_side.iter()),
+            Side::Sell => Box::new(self.bid_side.iter().rev()),
+        }
+    }
+
+    /// Checks an order's quantity against the book's maximum order quantity.
+    ///
+    /// # Errors:
+    /// - Returns [`QuantityTooLarge`](crate::error::OrderBookError)
+    fn check_order_quantity(&self, order: &Order) -> BookResult<()> {
+        match self.max_order_quantity {
+            Some(max_quantity) if *order.get_initial_quantity() > max_quantity => {
+                Err(QuantityTooLarge {
+                    order_id: *order.get_order_id(),
+                    quantity: *order.get_initial_quantity(),
+                    max_quantity,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Moves an order to the back of its level with a fresh sequence, as if newly added.
+    fn requeue_order(&mut self, orders: &mut OrderRefs, order_id: OrderId) {
+        orders.get_refresh(&order_id);
+        if let Some(order_entry) = self.track_orders.get_mut(&order_id) {
+            order_entry.seq = self.next_seq;
+            order_entry.filled_ahead = 0;
+            self.next_seq += 1;
+        }
+    }
+
+    /// Takes a message from `owner`'s rate limit, if the book has one.
+    ///
+    /// # Errors:
+    /// - Returns [`RateLimited`](crate::error::OrderBookError)
+    fn check_rate_limit(&mut self, owner: OwnerId) -> BookResult<()> {
+        let allowed = self
+            .rate_limiter
+            .as_mut()
+            .is_none_or(|rate_limiter| rate_limiter.try_acquire(owner));
+        match allowed {
+            true => Ok(()),
+            false => Err(RateLimited { owner }),
+        }
+    }
+
+    /// Reduces a resting order's total quantity in place, see [`Order::reduce_to`].
+    fn reduce_order(&mut self, order: &Order, quantity: Quantity) -> BookResult<()> {
+        let order_ref = self.get_order_ref(order.get_order_id())?.clone();
+        let mut resting_order = lock_order(&order_ref)?;
+        resting_order.reduce_to(quantity);
+        self.mirror_remaining_quantity(&resting_order);
+
+        let removed_quantity =
+            order.get_remaining_quantity() - resting_order.get_remaining_quantity();
+        // parked pegs aren't in the levels, so there's no visible change to report
+        if removed_quantity > 0 && self.track_orders.contains_key(order.get_order_id()) {
+            self.emit(BookEvent::OrderRemoved {
+                order_id: *order.get_order_id(),
+                side: *order.get_side(),
+                price: *order.get_price(),
+                quantity: removed_quantity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Copies an order's remaining quantity into its entry's lock-free mirror.
+    fn mirror_remaining_quantity(&self, order: &Order) {
+        if let Some(order_entry) = self.track_orders.get(order.get_order_id()) {
+            order_entry
+                .remaining_quantity
+                .store(*order.get_remaining_quantity() as u64, Relaxed);
+        }
+    }
+
+    /// Remaining quantity of a resting order.
+    /// Reads the mirrored quantity, only locking orders the book isn't tracking.
+    fn resting_quantity(&self, order_id: &OrderId, order: &OrderRef) -> Quantity {
+        match self.track_orders.get(order_id) {
+            Some(order_entry) => order_entry.remaining_quantity.load(Relaxed) as Quantity,
+            None => *read_order(order).get_remaining_quantity(),
+        }
+    }
+
+    /// Total remaining quantity resting at a price level.
+    fn level_quantity(&self, orders: &OrderRefs) -> Quantity {
+        orders
+            .iter()
+            .map(|(order_id, order)| self.resting_quantity(order_id, order))
+            .sum()
+    }
+
+    /// Remembers an emptied level for display, if levels are sticky. A level created and
+    /// emptied by the same operation, like a fully filled aggressor's, was never shown.
+    fn level_emptied(&mut self, side: Side, price: Price) {
+        if self.sticky_levels && !self.created_levels.contains(&(side, price)) {
+            self.emptied_levels.insert((side, price));
+        }
+    }
+
+    /// Level infos of `side`, lowest price first, with the sticky emptied levels that haven't
+    /// filled up again shown at zero quantity.
+    fn displayed_level_infos(&self, side: Side) -> LevelInfos {
+        let levels = self.side_levels(side);
+        let mut infos = self.level_infos(levels.iter());
+        let emptied = self
+            .emptied_levels
+            .range((side, Price::MIN)..=(side, Price::MAX))
+            .filter(|(_, price)| !levels.contains_key(price));
+        let level_count = infos.len();
+        infos.extend(emptied.map(|(_, price)| LevelInfo::new(*price, 0, 0)));
+        if infos.len() > level_count {
+            infos.sort_by_key(|level| level.price);
+        }
+        infos
+    }
+
+    /// Aggregates price levels into level infos, keeping the iteration order.
+    fn level_infos<'a>(
+        &self,
+        levels: impl Iterator<Item = (&'a Price, &'a OrderRefs)>,
+    ) -> LevelInfos {
+        levels
+            .map(|(price, orders)| LevelInfo {
+                price: *price,
+                quantity: self.level_quantity(orders),
+                order_count: orders.len(),
+            })
+            .collect()
+    }
+
+    /// Credits a fill of `order_id` to every order queued behind it in its level.
+    fn credit_filled_ahead(&mut self, orders: &OrderRefs, order_id: OrderId, quantity: Quantity) {
+        for behind_id in orders.keys().skip_while(|id| **id != order_id).skip(1) {
+            if let Some(order_entry) = self.track_orders.get_mut(behind_id) {
+                order_entry.filled_ahead = order_entry.filled_ahead.saturating_add(quantity);
+            }
+        }
+    }
+
+    /// Stops tracking a fully filled order, remembering its id for a while.
+    fn forget_filled_order(&mut self, order_id: OrderId) {
+        self.track_orders.remove(&order_id);
+        remember_order_id(&mut self.recently_filled, order_id);
+    }
+
+    /// Runs a public mutation, then tells the top of book listener if the best bid or ask moved.
+    /// Pegged orders are re-priced first, as the mutation may have moved the midpoint.
+    /// Meant for mutations that can't fail: they have nowhere to report a failed match of the
+    /// re-priced pegs, which stay resting for the next match pass to report.
+    fn notifying<T>(&mut self, mutation: impl FnOnce(&mut Self) -> T) -> T {
+        if self.mutating {
+            return mutation(self);
+        }
+
+        self.begin_mutation();
+        let result = mutation(self);
+        if let Err(error) = self.reprice_pegs(&mut vec![]) {
+            debug_assert!(
+                matches!(error, OrderBookError::LockPoisoned { .. }),
+                "re-pricing pegs failed: {error}"
+            );
+        }
+        self.end_mutation();
+        result
+    }
+
+    /// Like [`notifying`](Self::notifying), for mutations that can fail. Trades from matching
+    /// re-priced pegs are appended to `out` after the mutation's own, and an error from that
+    /// match is returned if the mutation itself succeeded.
+    fn notifying_into<T>(
+        &mut self,
+        out: &mut Trades,
+        mutation: impl FnOnce(&mut Self, &mut Trades) -> BookResult<T>,
+    ) -> BookResult<T> {
+        if self.mutating {
+            return mutation(self, out);
+        }
+
+        self.begin_mutation();
+        let result = mutation(self, out);
+        let repriced = self.reprice_pegs(out);
+        self.end_mutation();
+        let value = result?;
+        repriced?;
+        Ok(value)
+    }
+
+    /// Like [`notifying_into`](Self::notifying_into), for mutations returning their trades.
+    fn notifying_trades(
+        &mut self,
+        mutation: impl FnOnce(&mut Self) -> BookResult<Option<Trades>>,
+    ) -> BookResult<Option<Trades>> {
+        let mut trades: Trades = vec![];
+        self.notifying_into(&mut trades, |book, trades| {
+            trades.extend(mutation(book)?.unwrap_or_default());
+            Ok(())
+        })?;
+        Ok((!trades.is_empty()).then_some(trades))
+    }
+
+    /// Records the state a public mutation is compared against once it's done.
+    fn begin_mutation(&mut self) {
+        self.mutating = true;
+        self.pre_mutation_top_of_book = (self.best_bid(), self.best_ask());
+        self.created_levels.clear();
+    }
+
+    /// Ends a public mutation, telling the top of book listener if the best bid or ask moved.
+    fn end_mutation(&mut self) {
+        self.mutating = false;
+
+        if self.top_of_book_listener.is_none() {
+            return;
+        }
+        let top_of_book = (self.best_bid(), self.best_ask());
+        if top_of_book != self.last_top_of_book {
+            self.last_top_of_book = top_of_book;
+            if let Some(listener) = self.top_of_book_listener.as_mut() {
+                listener(top_of_book.0, top_of_book.1);
+            }
+        }
+    }
+
+    /// Best price on `side` among orders that aren't pegged, which pegged orders follow.
+    fn peg_reference(&self, side: Side) -> Option<Price> {
+        self.opposite_levels(side.opposite())
+            .find(|(_, orders)| {
+                orders
+                    .values()
+                    .any(|order| *read_order(order).get_order_type() != OrderType::PegMid)
+            })
+            .map(|(price, _)| *price)
+    }
+
+    /// Price of a pegged order on `side`: the midpoint, rounded away from the other side when
+    /// it falls between ticks. None if either side has nothing to peg to.
+    fn peg_price(&self, side: Side) -> Option<Price> {
+        let bid = self.peg_reference(Side::Buy)?;
+        let ask = self.peg_reference(Side::Sell)?;
+        Some(midpoint_on_tick(bid, ask, self.tick_size, side))
+    }
+
+    /// Moves pegged orders whose midpoint changed to their new level, parks those that lost
+    /// their midpoint and places parked ones that have one again, then matches once.
+    /// A peg the book no longer accepts, e.g. after its quantity limit was lowered, is parked
+    /// until it can be placed again. Trades this produces are appended to `trades`.
+    ///
+    /// # Errors:
+    /// - Returns [`FillOverflow`](crate::error::OrderBookError)
+    /// - Returns [`LockPoisoned`](crate::error::OrderBookError)
+    fn reprice_pegs(&mut self, trades: &mut Trades) -> BookResult<()> {
+        if self.pegged_ids.is_empty() && self.parked_pegs.is_empty() {
+            return Ok(());
+        }
+        let (bid_peg_price, ask_peg_price) =
+            (self.peg_price(Side::Buy), self.peg_price(Side::Sell));
+        let peg_price = |side: Side| match side {
+            Side::Buy => bid_peg_price,
+            Side::Sell => ask_peg_price,
+        };
+
+        self.pegged_ids
+            .retain(|order_id| self.track_orders.contains_key(order_id));
+        let mut stale: Vec<OrderId> = self
+            .pegged_ids
+            .iter()
+            .filter(|order_id| {
+                let order_entry = &self.track_orders[*order_id];
+                peg_price(order_entry.book_side) != Some(order_entry.price)
+            })
+            .copied()
+            .collect();
+        // moved pegs keep their time priority relative to each other
+        stale.sort_by_key(|order_id| self.track_orders[order_id].seq);
+
+        let mut moved: Vec<OrderRef> = vec![];
+        for order_id in stale {
+            if let Ok(order) = self.get_order_ref(&order_id).cloned() {
+                self.pegged_ids.remove(&order_id);
+                if self.remove_order(order_id).is_ok() {
+                    moved.push(order);
+                }
+            }
+        }
+        while let Some((_, order)) = self.parked_pegs.pop_front() {
+            moved.push(order);
+        }
+
+        let mut placed = false;
+        for order in moved {
+            match self.insert_order(&order) {
+                Ok(inserted) => placed |= inserted,
+                Err(_) => {
+                    let order_id = *read_order(&order).get_order_id();
+                    self.parked_pegs.insert(order_id, order);
+                }
+            }
+        }
+        if placed && self.phase == Phase::Continuous {
+            self.match_orders_into(trades)?;
+        }
+        Ok(())
+    }
+
+    /// Records an event if event recording is on.
+    fn emit(&mut self, event: BookEvent) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    /// Error describing why an order id isn't resting in the book.
+    fn missing_order_error(&self, order_id: OrderId) -> OrderBookError {
+        match self.recently_filled.contains_key(&order_id) {
+            true => OrderAlreadyFilled(order_id),
+            false => OrderNotFound(order_id),
+        }
+    }
+
+    /// Get shared reference to an order within book given its id.
+    ///
+    /// # Errors:
+    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
+    /// - Returns [`OrderAlreadyFilled`](crate::error::OrderBookError)
+    /// - Returns [`TrackingDesync`](crate::error::OrderBookError)
+    fn get_order_ref(&self, order_id: &OrderId) -> BookResult<&OrderRef> {
+        if let Some(order) = self
+            .parked_pegs
+            .get(order_id)
+            .or_else(|| self.scheduled_orders.get(order_id))
+        {
+            return Ok(order);
+        }
+
+        let order_entry = self
+            .track_orders
+            .get(order_id)
+            .ok_or_else(|| self.missing_order_error(*order_id))?;
+
+        let book_side = match order_entry.book_side {
+            Side::Buy => &self.bid_side,
+            Side::Sell => &self.ask_side,
+        };
+
+        let order = book_side
+            .get(&order_entry.price)
+            .ok_or(TrackingDesync {
+                order_id: *order_id,
+            })?
+            .get(order_id)
+            .ok_or(TrackingDesync {
+                order_id: *order_id,
+            })?;
+
+        Ok(order)
+    }
+}
+
+/// Records an order id, evicting the oldest once capacity is reached.
+fn remember_order_id(order_ids: &mut LinkedHashMap<OrderId, ()>, order_id: OrderId) {
+    order_ids.insert(order_id, ());
+    if order_ids.len() > RECENT_ORDER_IDS_CAPACITY {
+        order_ids.pop_front();
+    }
+}
+
+/// Locks an order for matching or modifying it.
+///
+/// # Errors:
+/// - Returns [`LockPoisoned`](crate::error::OrderBookError) if a thread panicked holding the lock
+fn lock_order(order: &OrderRef) -> BookResult<MutexGuard<'_, Order>> {
+    order.lock().map_err(|poisoned| LockPoisoned {
+        order_id: *poisoned.get_ref().get_order_id(),
+    })
+}
+
+/// Id of the order at a price level that the matcher consumes next, following `queue` if the
+/// level has one. Queued ids that have since left the level are dropped along the way.
+fn next_in_level(orders: &OrderRefs, queue: &mut Option<VecDeque<OrderId>>) -> Option<OrderId> {
+    let Some(queue) = queue else {
+        return orders.front().map(|(order_id, _)| *order_id);
+    };
+    while let Some(order_id) = queue.front() {
+        if orders.contains_key(order_id) {
+            return Some(*order_id);
+        }
+        queue.pop_front();
+    }
+    None
+}
+
+/// Moves a refreshed order to the back of its level's queue, like its requeue in the level.
+fn requeue_in_queue(queue: &mut Option<VecDeque<OrderId>>, order_id: OrderId) {
+    // the refreshed order is the one the matcher just consumed, at the front
+    if let Some(queue) = queue {
+        if queue.front() == Some(&order_id) {
+            queue.pop_front();
+        }
+        queue.push_back(order_id);
+    }
+}
+
+/// Midpoint of `bid` and `ask` on the `tick_size` grid. Between ticks it's rounded in favour
+/// of an order on `side`: down for a buyer, up for a seller.
+fn midpoint_on_tick(bid: Price, ask: Price, tick_size: Price, side: Side) -> Price {
+    let (sum, ticks) = (bid as i64 + ask as i64, 2 * tick_size as i64);
+    let midpoint = match side {
+        Side::Buy => sum.div_euclid(ticks),
+        Side::Sell => (sum + ticks - 1).div_euclid(ticks),
+    } * tick_size as i64;
+    midpoint.clamp(Price::MIN as i64, Price::MAX as i64) as Price
+}
+
+/// Locks an order to read it, even if a thread panicked holding the lock.
+/// Used by queries and cancels, so a poisoned order stays visible and can be taken out.
+fn read_order(order: &OrderRef) -> MutexGuard<'_, Order> {
+    order.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gtc(order_id: OrderId, side: Side, price: Price, quantity: Quantity) -> OrderRef {
+        Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity).to_order_ref()
+    }
+
+    fn levels(infos: &LevelInfos) -> Vec<(Price, Quantity)> {
+        infos
+            .iter()
+            .map(|level| (*level.get_price(), *level.get_quantity()))
+            .collect()
+    }
+
+    #[test]
+    fn test_add_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        assert!(book.add_order(gtc(1, Side::Buy, 100, 10))?.is_none());
+        assert!(book.add_order(gtc(2, Side::Sell, 105, 20))?.is_none());
+
+        let infos = book.get_order_infos();
+        assert_eq!(levels(infos.get_bids()), vec![(100, 10)]);
+        assert_eq!(levels(infos.get_asks()), vec![(105, 20)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_duplicate_order_id() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        let result = book.add_order(gtc(1, Side::Sell, 105, 20));
+
+        assert!(matches!(result, Err(OrderAlreadyExists(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_order_non_existent_id() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        assert!(matches!(book.cancel_order(1), Err(OrderNotFound(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 5))?;
+
+        assert_eq!(book.cancel_order(1)?, 1);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(100, 5)]);
+
+        book.cancel_order(2)?;
+        assert!(book.get_order_infos().get_bids().is_empty());
+        assert!(matches!(book.cancel_order(2), Err(OrderNotFound(2))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        assert_eq!(
+            book.modify_order(OrderModify::new(1, None, Some(101), Some(30)))?,
+            ModifyResult::Amended(None)
+        );
+
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 30)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_non_existent_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        let result = book.modify_order(OrderModify::new(1, None, Some(101), None));
+
+        assert!(matches!(result, Err(OrderNotFound(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_infos_book_empty_state() -> BookResult<()> {
+        let book = OrderBook::new("QQQ");
+
+        let infos = book.get_order_infos();
+        assert!(infos.get_bids().is_empty());
+        assert!(infos.get_asks().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_infos_book_non_empty_state() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 15))?;
+        book.add_order(gtc(3, Side::Buy, 99, 5))?;
+        book.add_order(gtc(4, Side::Sell, 102, 7))?;
+
+        let infos = book.get_order_infos();
+        assert_eq!(levels(infos.get_bids()), vec![(99, 5), (100, 25)]);
+        assert_eq!(levels(infos.get_asks()), vec![(102, 7)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_filled_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        let trades = book.add_order(gtc(2, Side::Sell, 100, 10))?;
+        assert_eq!(trades.map(|trades| trades.len()), Some(1));
+
+        assert!(matches!(book.cancel_order(1), Err(OrderAlreadyFilled(1))));
+        assert!(matches!(book.cancel_order(2), Err(OrderAlreadyFilled(2))));
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, Some(101), None)),
+            Err(OrderAlreadyFilled(1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recently_filled_is_bounded() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        for order_id in 0..=RECENT_ORDER_IDS_CAPACITY as OrderId {
+            book.forget_filled_order(order_id);
+        }
+
+        assert_eq!(book.recently_filled.len(), RECENT_ORDER_IDS_CAPACITY);
+        assert!(matches!(book.cancel_order(0), Err(OrderNotFound(0))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantity_in_range() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Sell, 101, 10))?;
+        book.add_order(gtc(2, Side::Sell, 102, 20))?;
+        book.add_order(gtc(3, Side::Sell, 102, 5))?;
+        book.add_order(gtc(4, Side::Sell, 103, 30))?;
+        book.add_order(gtc(5, Side::Sell, 105, 40))?;
+        book.add_order(gtc(6, Side::Buy, 100, 50))?;
+
+        assert_eq!(book.quantity_in_range(Side::Sell, 102, 104), 55);
+        assert_eq!(book.quantity_in_range(Side::Sell, 101, 101), 10);
+        assert_eq!(book.quantity_in_range(Side::Sell, 106, 110), 0);
+        assert_eq!(book.quantity_in_range(Side::Sell, 104, 102), 0);
+        assert_eq!(book.quantity_in_range(Side::Buy, 0, 200), 50);
+        Ok(())
+    }
+
+    #[test]
+    fn test_depth() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 98, 10))?;
+        book.add_order(gtc(2, Side::Buy, 99, 20))?;
+        book.add_order(gtc(3, Side::Buy, 100, 30))?;
+        book.add_order(gtc(4, Side::Sell, 101, 40))?;
+        book.add_order(gtc(5, Side::Sell, 102, 50))?;
+
+        let depth = book.depth(2);
+        assert_eq!(levels(depth.get_bids()), vec![(100, 30), (99, 20)]);
+        assert_eq!(levels(depth.get_asks()), vec![(101, 40), (102, 50)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_imbalance() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.imbalance(1), None);
+
+        book.add_order(gtc(1, Side::Buy, 100, 30))?;
+        book.add_order(gtc(2, Side::Buy, 99, 50))?;
+        assert_eq!(book.imbalance(1), None);
+
+        book.add_order(gtc(3, Side::Sell, 101, 10))?;
+        book.add_order(gtc(4, Side::Sell, 102, 100))?;
+
+        // (30 - 10) / (30 + 10)
+        assert_eq!(book.imbalance(1), Some(0.5));
+        // (80 - 110) / (80 + 110)
+        assert_eq!(book.imbalance(2), Some(-30.0 / 190.0));
+        assert_eq!(book.imbalance(0), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_sweep_matches_real_trades() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Sell, 101, 10))?;
+        book.add_order(gtc(2, Side::Sell, 101, 15))?;
+        book.add_order(gtc(3, Side::Sell, 102, 20))?;
+        book.add_order(gtc(4, Side::Sell, 104, 30))?;
+
+        let sweep = book.simulate_sweep(Side::Buy, 50);
+        assert_eq!(sweep.get_fills(), &vec![(101, 25), (102, 20), (104, 5)]);
+        assert_eq!(*sweep.get_filled_quantity(), 50);
+        assert_eq!(*sweep.get_unfilled_quantity(), 0);
+
+        // simulating leaves the book untouched
+        assert_eq!(book.quantity_in_range(Side::Sell, 0, 200), 75);
+
+        let trades = book.add_order(gtc(5, Side::Buy, 104, 50))?.unwrap();
+        let mut real_fills: Vec<(Price, Quantity)> = vec![];
+        for trade in trades.iter().map(|trade| trade.get_ask_trade()) {
+            match real_fills.last_mut() {
+                Some((price, quantity)) if *price == trade.price => *quantity += trade.quantity,
+                _ => real_fills.push((trade.price, trade.quantity)),
+            }
+        }
+        assert_eq!(&real_fills, sweep.get_fills());
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulate_sweep_unfilled_remainder() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 99, 20))?;
+
+        let sweep = book.simulate_sweep(Side::Sell, 45);
+        assert_eq!(sweep.get_fills(), &vec![(100, 10), (99, 20)]);
+        assert_eq!(*sweep.get_filled_quantity(), 30);
+        assert_eq!(*sweep.get_unfilled_quantity(), 15);
+        Ok(())
+    }
+
+    #[test]
+    fn test_end_of_day_cancels_good_for_day_orders() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let gfd = |order_id, side, price, quantity| {
+            Order::new(OrderType::GoodForDay, order_id, side, price, quantity).to_order_ref()
+        };
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gfd(2, Side::Buy, 100, 20))?;
+        book.add_order(gfd(3, Side::Buy, 99, 30))?;
+        book.add_order(gtc(4, Side::Sell, 105, 40))?;
+        book.add_order(gfd(5, Side::Sell, 106, 50))?;
+
+        let mut cancelled = book.end_of_day();
+        cancelled.sort();
+        assert_eq!(cancelled, vec![2, 3, 5]);
+
+        let infos = book.get_order_infos();
+        assert_eq!(levels(infos.get_bids()), vec![(100, 10)]);
+        assert_eq!(levels(infos.get_asks()), vec![(105, 40)]);
+        assert_eq!(book.track_orders.len(), 2);
+        assert!(book.end_of_day().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_skips_zero_remaining_orders() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        // resting orders whose remaining quantity was already exhausted
+        let empty_bid = gtc(1, Side::Buy, 100, 10);
+        empty_bid.lock().unwrap().fill(10)?;
+        let empty_ask = gtc(2, Side::Sell, 100, 10);
+        empty_ask.lock().unwrap().fill(10)?;
+        book.bid_side.entry(100).or_default().insert(1, empty_bid);
+        book.ask_side.entry(100).or_default().insert(2, empty_ask);
+
+        assert!(book.match_orders()?.is_none());
+        assert!(book.bid_side.is_empty());
+        assert!(book.ask_side.is_empty());
+
+        book.add_order(gtc(3, Side::Buy, 100, 0))?;
+        let trades = book.add_order(gtc(4, Side::Sell, 100, 5))?;
+        assert!(trades.is_none());
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(100, 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_level_ordering() -> BookResult<()> {
+        for (level_ordering, filled_id) in
+            [(LevelOrdering::Time, 1), (LevelOrdering::SizeThenTime, 2)]
+        {
+            let mut book = OrderBook::new("QQQ");
+            book.set_level_ordering(level_ordering);
+
+            book.add_order(gtc(1, Side::Sell, 100, 50))?;
+            book.add_order(gtc(2, Side::Sell, 100, 200))?;
+            let trades = book.add_order(gtc(3, Side::Buy, 100, 50))?.unwrap();
+
+            assert_eq!(trades.len(), 1);
+            assert_eq!(trades[0].get_ask_trade().order_id, filled_id);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_then_time_breaks_ties_by_time() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_level_ordering(LevelOrdering::SizeThenTime);
+
+        book.add_order(gtc(1, Side::Buy, 100, 30))?;
+        book.add_order(gtc(2, Side::Buy, 100, 80))?;
+        book.add_order(gtc(3, Side::Buy, 100, 80))?;
+        let trades = book.add_order(gtc(4, Side::Sell, 100, 170))?.unwrap();
+
+        let bid_ids: Vec<OrderId> = trades
+            .iter()
+            .map(|trade| trade.get_bid_trade().order_id)
+            .collect();
+        assert_eq!(bid_ids, vec![2, 3, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_then_time_requeues_refreshed_reserve() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_level_ordering(LevelOrdering::SizeThenTime);
+
+        book.submit(
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 100, 60).with_reserve(40),
+        )?;
+        book.add_order(gtc(2, Side::Sell, 100, 30))?;
+        let trades = book.add_order(gtc(3, Side::Buy, 100, 80))?.unwrap();
+
+        // order 1's refreshed slice goes behind order 2 for the rest of the sweep
+        let ask_fills: Vec<(OrderId, Quantity)> = trades
+            .iter()
+            .map(|trade| (trade.get_ask_trade().order_id, *trade.get_quantity()))
+            .collect();
+        assert_eq!(ask_fills, vec![(1, 40), (2, 30), (1, 10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_order_ids() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 105, 10))?;
+        book.add_order(gtc(3, Side::Buy, 99, 10))?;
+        book.cancel_order(1)?;
+
+        let mut open_ids = book.open_order_ids();
+        open_ids.sort();
+        assert_eq!(open_ids, vec![2, 3]);
+        assert_eq!(book.open_order_ids_side(Side::Buy), vec![3]);
+        assert_eq!(book.open_order_ids_side(Side::Sell), vec![2]);
+
+        // filled orders are no longer open
+        book.add_order(gtc(4, Side::Buy, 105, 10))?;
+        assert_eq!(book.open_order_ids(), vec![3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_matches_add_order() -> BookResult<()> {
+        let orders = [
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 10),
+            Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 102, 20),
+            Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 102, 25),
+        ];
+
+        let mut submitted = OrderBook::new("QQQ");
+        let mut added = OrderBook::new("QQQ");
+        for order in orders {
+            let submit_trades = submitted.submit(order.clone())?;
+            let add_trades = added.add_order(order.to_order_ref())?;
+            assert_eq!(format!("{:?}", submit_trades), format!("{:?}", add_trades));
+        }
+
+        assert_eq!(
+            levels(submitted.get_order_infos().get_asks()),
+            levels(added.get_order_infos().get_asks())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_traded_volume_and_notional() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 10000, 100))?;
+        book.add_order(gtc(2, Side::Sell, 10100, 50))?;
+        assert_eq!(book.traded_volume(), 0);
+        assert_eq!(book.traded_notional(), 0);
+
+        book.add_order(gtc(3, Side::Sell, 10000, 100))?;
+        assert_eq!(book.traded_volume(), 100);
+        assert_eq!(book.traded_notional(), 1_000_000);
+
+        // aggressive buy at 10200 is valued at the resting ask's 10100
+        book.add_order(gtc(4, Side::Buy, 10200, 50))?;
+        assert_eq!(book.traded_volume(), 150);
+        assert_eq!(book.traded_notional(), 1_000_000 + 505_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_replace() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 102, 5))?;
+
+        let trades = book.cancel_replace(
+            1,
+            Order::new(OrderType::GoodTillCancel, 3, Side::Buy, 102, 20),
+        )?;
+        assert_eq!(trades.map(|trades| trades.len()), Some(1));
+
+        let mut open_ids = book.open_order_ids();
+        open_ids.sort();
+        assert_eq!(open_ids, vec![3]);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(102, 15)]);
+        assert!(matches!(book.cancel_order(1), Err(OrderNotFound(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_replace_duplicate_new_id_keeps_old_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 99, 10))?;
+
+        let result = book.cancel_replace(
+            1,
+            Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 101, 20),
+        );
+        assert!(matches!(result, Err(OrderAlreadyExists(2))));
+        assert_eq!(
+            levels(book.get_order_infos().get_bids()),
+            vec![(99, 10), (100, 10)]
+        );
+
+        let result = book.cancel_replace(
+            7,
+            Order::new(OrderType::GoodTillCancel, 8, Side::Buy, 101, 20),
+        );
+        assert!(matches!(result, Err(OrderNotFound(7))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_bid_and_ask() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!((book.best_bid(), book.best_ask()), (None, None));
+
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 10))?;
+        book.add_order(gtc(3, Side::Sell, 103, 10))?;
+        book.add_order(gtc(4, Side::Sell, 102, 10))?;
+
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(100), Some(102)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_microprice() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 30))?;
+        assert_eq!(book.microprice(), None);
+
+        book.add_order(gtc(2, Side::Sell, 102, 10))?;
+        book.add_order(gtc(3, Side::Sell, 103, 500))?;
+
+        // (100 * 10 + 102 * 30) / 40, heavier bids push it above the 101 midpoint
+        let microprice = book.microprice().unwrap();
+        assert_eq!(microprice, 101.5);
+        assert!(microprice > 101.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_match() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert!(book.next_match(Side::Buy).is_none());
+
+        book.add_order(gtc(1, Side::Sell, 102, 10))?;
+        book.add_order(gtc(2, Side::Sell, 101, 10))?;
+        book.add_order(gtc(3, Side::Sell, 101, 50))?;
+        book.add_order(gtc(4, Side::Buy, 99, 10))?;
+        book.add_order(gtc(5, Side::Buy, 100, 10))?;
+
+        let next_ask = book.next_match(Side::Buy).unwrap();
+        assert_eq!(*next_ask.lock().unwrap().get_order_id(), 2);
+        let next_bid = book.next_match(Side::Sell).unwrap();
+        assert_eq!(*next_bid.lock().unwrap().get_order_id(), 5);
+
+        book.set_level_ordering(LevelOrdering::SizeThenTime);
+        let next_ask = book.next_match(Side::Buy).unwrap();
+        assert_eq!(*next_ask.lock().unwrap().get_order_id(), 3);
+        assert_eq!(book.open_order_ids().len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_overfill_reports_fill_overflow() -> BookResult<()> {
+        let order = gtc(7, Side::Buy, 100, 10);
+
+        let err: OrderBookError = order.lock().unwrap().fill(25).unwrap_err().into();
+
+        assert!(matches!(
+            err,
+            FillOverflow {
+                order_id: 7,
+                surplus: 15
+            }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracking_desync() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.track_orders.get_mut(&1).unwrap().price = 101;
+
+        assert!(matches!(
+            book.cancel_order(1),
+            Err(TrackingDesync { order_id: 1 })
+        ));
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, None, Some(5))),
+            Err(TrackingDesync { order_id: 1 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_level_orders() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 25))?;
+        book.add_order(gtc(3, Side::Buy, 99, 5))?;
+        book.add_order(gtc(4, Side::Buy, 100, 40))?;
+        book.add_order(gtc(5, Side::Sell, 100, 6))?;
+
+        assert_eq!(
+            book.level_orders(Side::Buy, 100),
+            Some(vec![(1, 4), (2, 25), (4, 40)])
+        );
+        assert_eq!(book.level_orders(Side::Buy, 99), Some(vec![(3, 5)]));
+        assert_eq!(book.level_orders(Side::Buy, 98), None);
+        assert_eq!(book.level_orders(Side::Sell, 100), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_price_book() -> BookResult<()> {
+        let mut book = OrderBook::new("SPRD");
+
+        book.add_order(gtc(1, Side::Sell, -60, 10))?;
+        book.add_order(gtc(2, Side::Sell, -55, 10))?;
+        book.add_order(gtc(3, Side::Buy, -70, 10))?;
+        book.add_order(gtc(4, Side::Buy, -65, 10))?;
+
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(-65), Some(-60)));
+        let depth = book.depth(2);
+        assert_eq!(levels(depth.get_bids()), vec![(-65, 10), (-70, 10)]);
+        assert_eq!(levels(depth.get_asks()), vec![(-60, 10), (-55, 10)]);
+
+        // a buy at -50 crosses the -60 ask and executes at the resting price
+        let trades = book.add_order(gtc(5, Side::Buy, -50, 10))?.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].get_ask_trade().order_id, 1);
+        assert_eq!(trades[0].get_ask_trade().price, -60);
+        assert_eq!(book.traded_notional(), -600);
+        assert_eq!(book.best_ask(), Some(-55));
+        Ok(())
+    }
+
+    #[test]
+    fn test_book_straddling_zero() -> BookResult<()> {
+        let mut book = OrderBook::new("SPRD");
+
+        book.add_order(gtc(1, Side::Buy, -5, 10))?;
+        book.add_order(gtc(2, Side::Buy, 0, 10))?;
+        book.add_order(gtc(3, Side::Sell, 3, 10))?;
+        book.add_order(gtc(4, Side::Sell, 1, 10))?;
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(0), Some(1)));
+        assert_eq!(book.quantity_in_range(Side::Buy, -5, -1), 10);
+
+        // a sell at -5 sweeps both bids, best (0) first
+        let trades = book.add_order(gtc(5, Side::Sell, -5, 15))?.unwrap();
+        let fills: Vec<(OrderId, Price, Quantity)> = trades
+            .iter()
+            .map(|trade| trade.get_bid_trade())
+            .map(|bid| (bid.order_id, bid.price, bid.quantity))
+            .collect();
+        assert_eq!(fills, vec![(2, 0, 10), (1, -5, 5)]);
+        assert_eq!(book.traded_notional(), -25);
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(-5), Some(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_report() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let owned = |order_id, side, price, quantity, owner| {
+            Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity).with_owner(owner)
+        };
+
+        book.submit(owned(1, Side::Sell, 100, 30, 7))?;
+        book.submit(owned(2, Side::Sell, 102, 30, 7))?;
+        book.submit(owned(3, Side::Buy, 105, 40, 9))?;
+
+        let trades = book.get_trade_history();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].get_bid_trade().owner, 9);
+        assert_eq!(trades[0].get_ask_trade().owner, 7);
+        assert_eq!(
+            trades
+                .iter()
+                .map(|trade| *trade.get_price())
+                .collect::<Vec<_>>(),
+            vec![100, 102]
+        );
+
+        // 30 @ 100 and 10 @ 102
+        let vwap = (30.0 * 100.0 + 10.0 * 102.0) / 40.0;
+        assert_eq!(
+            book.fill_report(),
+            vec![
+                FillReportEntry {
+                    owner: 7,
+                    net_quantity: -40,
+                    vwap
+                },
+                FillReportEntry {
+                    owner: 9,
+                    net_quantity: 40,
+                    vwap
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_auction_phase_matches_on_uncross() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_phase(Phase::Auction)?;
+
+        assert!(book.add_order(gtc(1, Side::Buy, 102, 10))?.is_none());
+        assert!(book.add_order(gtc(2, Side::Sell, 100, 15))?.is_none());
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(102), Some(100)));
+        assert_eq!(book.traded_volume(), 0);
+
+        let trades = book.uncross()?.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(*trades[0].get_quantity(), 10);
+        assert_eq!(book.get_phase(), &Phase::Auction);
+        assert_eq!((book.best_bid(), book.best_ask()), (None, Some(100)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_returning_to_continuous_matches() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_phase(Phase::Auction)?;
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 100, 10))?;
+        assert!(book.set_phase(Phase::Auction)?.is_none());
+
+        let trades = book.set_phase(Phase::Continuous)?;
+        assert_eq!(trades.map(|trades| trades.len()), Some(1));
+        assert!(book.open_order_ids().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_is_unaffected_by_later_changes() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 102, 20))?;
+        let snapshot = book.snapshot();
+        let reader_snapshot = Arc::clone(&snapshot);
+
+        book.add_order(gtc(3, Side::Buy, 102, 5))?;
+        book.cancel_order(1)?;
+        book.add_order(gtc(4, Side::Buy, 99, 40))?;
+
+        let reader = std::thread::spawn(move || {
+            (
+                levels(reader_snapshot.get_bids()),
+                levels(reader_snapshot.get_asks()),
+            )
+        });
+        assert_eq!(reader.join().unwrap(), (vec![(100, 10)], vec![(102, 20)]));
+        assert_eq!(snapshot.get_asset(), "QQQ");
+        assert_eq!(levels(book.snapshot().get_bids()), vec![(99, 40)]);
+        assert_eq!(levels(book.snapshot().get_asks()), vec![(102, 15)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantity_to_price() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.add_order(gtc(1, Side::Sell, 101, 10))?;
+        book.add_order(gtc(2, Side::Sell, 102, 20))?;
+        book.add_order(gtc(3, Side::Sell, 103, 30))?;
+        book.add_order(gtc(4, Side::Buy, 100, 15))?;
+        book.add_order(gtc(5, Side::Buy, 98, 25))?;
+
+        assert_eq!(book.quantity_to_price(Side::Sell, 101), 0);
+        assert_eq!(book.quantity_to_price(Side::Sell, 103), 30);
+        assert_eq!(book.quantity_to_price(Side::Sell, 110), 60);
+        assert_eq!(book.quantity_to_price(Side::Buy, 99), 15);
+        assert_eq!(book.quantity_to_price(Side::Buy, 98), 15);
+        assert_eq!(book.quantity_to_price(Side::Buy, 90), 40);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reusing_recent_order_ids() -> BookResult<()> {
+        for strict_order_ids in [false, true] {
+            let mut book = OrderBook::new("QQQ");
+            book.set_strict_order_ids(strict_order_ids);
+
+            book.add_order(gtc(1, Side::Buy, 100, 10))?;
+            book.cancel_order(1)?;
+            let readded = book.add_order(gtc(1, Side::Buy, 100, 10));
+
+            book.add_order(gtc(2, Side::Sell, 105, 10))?;
+            book.add_order(gtc(3, Side::Buy, 105, 10))?;
+            let refilled = book.add_order(gtc(2, Side::Sell, 105, 10));
+
+            if strict_order_ids {
+                assert!(matches!(readded, Err(OrderIdRecentlyUsed(1))));
+                assert!(matches!(refilled, Err(OrderIdRecentlyUsed(2))));
+            } else {
+                assert!(readded.is_ok());
+                assert!(refilled.is_ok());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_in_strict_mode_keeps_id() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_strict_order_ids(true);
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.modify_order(OrderModify::new(1, None, Some(101), None))?;
+
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_order_into_reuses_buffer() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let mut trades: Trades = Vec::with_capacity(8);
+        let buffer = trades.as_ptr();
+
+        book.add_order_into(gtc(1, Side::Sell, 100, 10), &mut trades)?;
+        book.add_order_into(gtc(2, Side::Sell, 101, 10), &mut trades)?;
+        assert!(trades.is_empty());
+
+        book.add_order_into(gtc(3, Side::Buy, 100, 4), &mut trades)?;
+        book.add_order_into(gtc(4, Side::Buy, 101, 10), &mut trades)?;
+        book.add_order_into(gtc(5, Side::Buy, 101, 10), &mut trades)?;
+
+        let fills: Vec<(OrderId, OrderId, Quantity)> = trades
+            .iter()
+            .map(|trade| {
+                (
+                    trade.get_bid_trade().order_id,
+                    trade.get_ask_trade().order_id,
+                    *trade.get_quantity(),
+                )
+            })
+            .collect();
+        assert_eq!(fills, vec![(3, 1, 4), (4, 1, 6), (4, 2, 4), (5, 2, 6)]);
+        assert_eq!(trades.as_ptr(), buffer);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 4)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_and_kill_sell() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let fak = |order_id, side, price, quantity| {
+            Order::new(OrderType::FillAndKill, order_id, side, price, quantity).to_order_ref()
+        };
+
+        book.add_order(gtc(1, Side::Buy, 98, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 10))?;
+        book.add_order(gtc(3, Side::Sell, 103, 10))?;
+
+        // only the lowest bid was compared before, which rejected this sell outright
+        let trades = book.add_order(fak(4, Side::Sell, 99, 25))?.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].get_bid_trade().order_id, 2);
+
+        // the remainder doesn't rest
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(103, 10)]);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(98, 10)]);
+        assert!(matches!(book.cancel_order(4), Err(OrderNotFound(4))));
+
+        assert!(book.add_order(fak(5, Side::Sell, 99, 5))?.is_none());
+        assert!(book.add_order(fak(6, Side::Buy, 102, 5))?.is_none());
+        assert_eq!(book.open_order_ids().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserve_order_refreshes_behind_queue() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        book.submit(
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 100, 50).with_reserve(20),
+        )?;
+        book.add_order(gtc(2, Side::Sell, 100, 10))?;
+        assert_eq!(
+            book.level_orders(Side::Sell, 100),
+            Some(vec![(1, 20), (2, 10)])
+        );
+
+        // visible 20 fills, the refreshed slice goes behind order 2
+        let trades = book.add_order(gtc(3, Side::Buy, 100, 25))?.unwrap();
+        let fills: Vec<(OrderId, Quantity)> = trades
+            .iter()
+            .map(|trade| (trade.get_ask_trade().order_id, *trade.get_quantity()))
+            .collect();
+        assert_eq!(fills, vec![(1, 20), (2, 5)]);
+        assert_eq!(
+            book.level_orders(Side::Sell, 100),
+            Some(vec![(2, 5), (1, 20)])
+        );
+
+        // sweeping the rest refreshes repeatedly until the reserve is exhausted
+        let trades = book.add_order(gtc(4, Side::Buy, 100, 100))?.unwrap();
+        let fills: Vec<(OrderId, Quantity)> = trades
+            .iter()
+            .map(|trade| (trade.get_ask_trade().order_id, *trade.get_quantity()))
+            .collect();
+        assert_eq!(fills, vec![(2, 5), (1, 20), (1, 10)]);
+        assert_eq!(book.traded_volume(), 60);
+        assert_eq!(book.get_order_infos().get_asks().len(), 0);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(100, 65)]);
+        assert!(matches!(book.cancel_order(1), Err(OrderAlreadyFilled(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_order_quantity() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_max_order_quantity(Some(100));
+
+        book.add_order(gtc(1, Side::Buy, 100, 100))?;
+        assert!(matches!(
+            book.add_order(gtc(2, Side::Buy, 100, 101)),
+            Err(QuantityTooLarge {
+                order_id: 2,
+                quantity: 101,
+                max_quantity: 100
+            })
+        ));
+
+        book.modify_order(OrderModify::new(1, None, Some(99), Some(100)))?;
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, Some(98), Some(101))),
+            Err(QuantityTooLarge { order_id: 1, .. })
+        ));
+        // the rejected modify leaves the order resting untouched
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(99, 100)]);
+
+        book.set_max_order_quantity(None);
+        book.add_order(gtc(2, Side::Buy, 100, 1_000_000))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_resting_vwap() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.resting_vwap(Side::Buy), None);
+
+        book.add_order(gtc(1, Side::Buy, 100, 30))?;
+        book.add_order(gtc(2, Side::Buy, 98, 10))?;
+        book.add_order(gtc(3, Side::Sell, 105, 5))?;
+
+        // (100 * 30 + 98 * 10) / 40
+        assert_eq!(book.resting_vwap(Side::Buy), Some(99.5));
+        assert_eq!(book.resting_vwap(Side::Sell), Some(105.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_notional_exceeding_i64() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        for order_id in [1, 3] {
+            book.add_order(gtc(order_id, Side::Sell, Price::MAX, Quantity::MAX))?;
+            book.add_order(gtc(order_id + 1, Side::Buy, Price::MAX, Quantity::MAX))?;
+        }
+
+        let trade_notional = Price::MAX as i128 * Quantity::MAX as i128;
+        assert_eq!(book.get_trade_history()[0].get_notional(), trade_notional);
+        assert!(book.traded_notional() > i64::MAX as i128);
+        assert_eq!(book.traded_notional(), 2 * trade_notional);
+        assert_eq!(book.fill_report()[0].vwap, Price::MAX as f64);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_match_orders_traced() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_phase(Phase::Auction)?;
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 10))?;
+        book.add_order(gtc(3, Side::Buy, 101, 5))?;
+        // same price, but the larger quantity sends order 1 to the back of its level
+        book.modify_order(OrderModify::new(1, None, Some(100), Some(11)))?;
+        book.add_order(gtc(4, Side::Sell, 100, 22))?;
+
+        let (trades, consumed) = book.match_orders_traced()?;
+        assert_eq!(consumed, vec![3, 2, 1]);
+        assert_eq!(trades.len(), 3);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(100, 4)]);
+
+        // nothing left to match
+        assert_eq!(book.match_orders_traced()?.1, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_level() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 105, 10))?;
+        book.add_order(gtc(2, Side::Sell, 105, 20))?;
+        book.add_order(gtc(3, Side::Sell, 105, 30))?;
+        book.add_order(gtc(4, Side::Sell, 106, 40))?;
+
+        assert_eq!(book.cancel_level(Side::Sell, 105), vec![1, 2, 3]);
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(106, 40)]);
+        assert!(matches!(book.cancel_order(2), Err(OrderNotFound(2))));
+        assert_eq!(book.open_order_ids(), vec![4]);
+
+        assert_eq!(book.cancel_level(Side::Buy, 105), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_resting_crossed_book() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let order = |order_id, side, price, quantity| {
+            Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity)
+        };
+
+        book.load_resting(vec![
+            order(1, Side::Buy, 101, 10),
+            order(2, Side::Buy, 99, 10),
+            order(3, Side::Sell, 100, 4),
+            order(4, Side::Sell, 102, 10),
+        ])?;
+
+        assert!(book.get_trade_history().is_empty());
+        assert_eq!(book.best_bid(), Some(101));
+        assert_eq!(book.best_ask(), Some(100));
+
+        let trades = book.uncross()?.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(*trades[0].get_quantity(), 4);
+        assert_eq!(
+            levels(book.get_order_infos().get_bids()),
+            vec![(99, 10), (101, 6)]
+        );
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(102, 10)]);
+
+        assert!(matches!(
+            book.load_resting(vec![order(4, Side::Sell, 103, 1)]),
+            Err(OrderAlreadyExists(4))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_spread_bps() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 9999, 10))?;
+        assert_eq!(book.spread_bps(), None);
+
+        book.add_order(gtc(2, Side::Sell, 10001, 10))?;
+        assert_eq!(book.spread(), Some(2));
+        assert_eq!(book.mid_price(), Some(10000.0));
+        assert_eq!(book.spread_bps(), Some(2.0));
+
+        // mid of zero with negative prices
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, -1, 10))?;
+        book.add_order(gtc(2, Side::Sell, 1, 10))?;
+        assert_eq!(book.mid_price(), Some(0.0));
+        assert_eq!(book.spread_bps(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_post_only_modify_rejected_when_crossing() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 102, 10))?;
+
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, Some(102), None).with_post_only()),
+            Err(WouldCross {
+                order_id: 1,
+                price: 102
+            })
+        ));
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(100, 10)]);
+        assert!(book.get_trade_history().is_empty());
+
+        // passive re-pricing is still allowed
+        book.modify_order(OrderModify::new(1, None, Some(101), None).with_post_only())?;
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_side() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let quote = |order_id, price, quantity| {
+            Order::new(
+                OrderType::GoodTillCancel,
+                order_id,
+                Side::Buy,
+                price,
+                quantity,
+            )
+        };
+        book.submit(quote(1, 100, 10).with_owner(7))?;
+        book.submit(quote(2, 99, 10).with_owner(7))?;
+        book.submit(quote(3, 99, 5).with_owner(8))?;
+        book.submit(Order::new(OrderType::GoodTillCancel, 4, Side::Sell, 102, 4))?;
+
+        // an order on the wrong side rejects the whole batch
+        let wrong_side = Order::new(OrderType::GoodTillCancel, 12, Side::Sell, 103, 10);
+        assert!(matches!(
+            book.replace_side(Side::Buy, 7, vec![quote(10, 101, 10), wrong_side]),
+            Err(ModificationError(_))
+        ));
+        assert_eq!(
+            levels(book.get_order_infos().get_bids()),
+            vec![(99, 15), (100, 10)]
+        );
+
+        let trades = book.replace_side(
+            Side::Buy,
+            7,
+            vec![quote(1, 101, 10), quote(10, 102, 3), quote(11, 98, 20)],
+        )?;
+        assert_eq!(trades.unwrap().len(), 1);
+        assert_eq!(
+            levels(book.get_order_infos().get_bids()),
+            vec![(98, 20), (99, 5), (101, 10)]
+        );
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(102, 1)]);
+        assert_eq!(book.level_orders(Side::Buy, 99), Some(vec![(3, 5)]));
+        assert_eq!(book.fill_report()[1].owner, 7);
+
+        // an id held by a scheduled order rejects the whole batch too
+        book.submit(Order::new(
+            OrderType::GoodAfterTime(50),
+            9,
+            Side::Buy,
+            90,
+            1,
+        ))?;
+        assert!(matches!(
+            book.replace_side(Side::Buy, 7, vec![quote(12, 97, 10), quote(9, 96, 10)]),
+            Err(OrderAlreadyExists(9))
+        ));
+        assert_eq!(
+            levels(book.get_order_infos().get_bids()),
+            vec![(98, 20), (99, 5), (101, 10)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_modification_rejected() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+
+        book.modify_order(OrderModify::new(1, None, Some(101), None).with_client_seq(2))?;
+        // arrives late, after the modify with sequence 2
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, Some(99), None).with_client_seq(1)),
+            Err(StaleModification {
+                order_id: 1,
+                client_seq: 1,
+                last_client_seq: 2
+            })
+        ));
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, Some(99), None).with_client_seq(2)),
+            Err(StaleModification { .. })
+        ));
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 10)]);
+
+        // modifies without a sequence don't reset the last applied one
+        book.modify_order(OrderModify::new(1, None, None, Some(20)))?;
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, None, Some(5)).with_client_seq(2)),
+            Err(StaleModification { .. })
+        ));
+        book.modify_order(OrderModify::new(1, None, Some(98), None).with_client_seq(3))?;
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(98, 20)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        assert!(book.quote(7, 1, 2, 10000, 5, 100)?.is_none());
+        assert_eq!(book.level_orders(Side::Buy, 9995), Some(vec![(1, 100)]));
+        assert_eq!(book.level_orders(Side::Sell, 10005), Some(vec![(2, 100)]));
+
+        // a second quote centred below the first lifts the resting bid
+        let trades = book.quote(8, 3, 4, 9990, 5, 40)?.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(*trades[0].get_price(), 9995);
+        assert_eq!(trades[0].get_ask_trade().owner, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_quote_places_nothing() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(5, Side::Sell, 10000, 10))?;
+
+        // the bid would lift order 5, but the ask reuses its id
+        assert!(matches!(
+            book.quote(7, 1, 5, 10000, 0, 10),
+            Err(OrderAlreadyExists(5))
+        ));
+        assert!(matches!(
+            book.quote(7, 1, 1, 9000, 5, 10),
+            Err(OrderAlreadyExists(1))
+        ));
+        assert!(book.get_trade_history().is_empty());
+        assert_eq!(book.open_order_ids(), vec![5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filled_ahead() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 100, 10))?;
+        book.add_order(gtc(3, Side::Sell, 100, 10))?;
+        assert_eq!(book.filled_ahead(3)?, 0);
+
+        book.add_order(gtc(4, Side::Buy, 100, 4))?;
+        assert_eq!(book.filled_ahead(2)?, 4);
+        assert_eq!(book.filled_ahead(3)?, 4);
+
+        // order 1 fills completely, order 2 partially
+        book.add_order(gtc(5, Side::Buy, 100, 9))?;
+        assert_eq!(book.filled_ahead(2)?, 10);
+        assert_eq!(book.filled_ahead(3)?, 13);
+
+        // cancels ahead don't count as fills
+        book.cancel_order(2)?;
+        assert_eq!(book.filled_ahead(3)?, 13);
+        assert!(matches!(book.filled_ahead(1), Err(OrderAlreadyFilled(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_volume_by_price() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 10000, 9))?;
+        book.add_order(gtc(2, Side::Sell, 10010, 10))?;
+        book.add_order(gtc(3, Side::Buy, 10000, 4))?;
+        book.add_order(gtc(4, Side::Buy, 10000, 5))?;
+        book.add_order(gtc(5, Side::Buy, 10010, 8))?;
+
+        assert_eq!(book.get_trade_history().len(), 3);
+        assert_eq!(
+            book.volume_by_price(),
+            BTreeMap::from([(10000, 9), (10010, 8)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_poisoned_order_lock() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let poisoned = gtc(1, Side::Sell, 100, 10);
+        book.add_order(poisoned.clone())?;
+
+        let handle = poisoned.clone();
+        let _ = std::thread::spawn(move || {
+            let _order = handle.lock().unwrap();
+            panic!("poisoning order 1");
+        })
+        .join();
+        assert!(poisoned.is_poisoned());
+
+        assert!(matches!(
+            book.add_order(gtc(2, Side::Buy, 100, 4)),
+            Err(LockPoisoned { order_id: 1 })
+        ));
+        assert!(matches!(
+            book.modify_order(OrderModify::new(1, None, Some(101), None)),
+            Err(LockPoisoned { order_id: 1 })
+        ));
+        assert!(matches!(
+            book.add_order(poisoned.clone()),
+            Err(LockPoisoned { order_id: 1 })
+        ));
+        // nothing was lost, and the poisoned order can still be taken out
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(100, 10)]);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(100, 4)]);
+        book.cancel_order(1)?;
+
+        book.add_order(gtc(3, Side::Sell, 100, 4))?;
+        assert_eq!(book.get_trade_history().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_effective_spread() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+        book.add_order(gtc(2, Side::Sell, 101, 5))?;
+        assert_eq!(book.simulate_sweep(Side::Buy, 0).vwap(), None);
+        book.add_order(gtc(3, Side::Sell, 103, 10))?;
+
+        // buying 10 fills 5 at 101 and 5 at 103, a vwap of 102 against a mid of 100
+        assert_eq!(book.effective_spread(Side::Buy, 10), Some(4.0));
+        assert_eq!(book.effective_spread(Side::Sell, 5), Some(2.0));
+        assert_eq!(book.effective_spread(Side::Buy, 16), None);
+        assert_eq!(book.effective_spread(Side::Buy, 0), None);
+
+        book.cancel_order(1)?;
+        assert_eq!(book.effective_spread(Side::Buy, 10), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_cannot_create_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+
+        assert!(matches!(
+            book.modify_order(OrderModify::new(2, Some(Side::Buy), Some(100), Some(10))),
+            Err(OrderNotFound(2))
+        ));
+        assert_eq!(book.open_order_ids(), vec![1]);
+
+        book.modify_order(OrderModify::new(1, None, Some(99), None))?;
+        assert_eq!(book.level_orders(Side::Buy, 99), Some(vec![(1, 10)]));
+        assert_eq!(book.open_order_ids(), vec![1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_crossing_add_rests() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+
+        assert!(book.add_order(gtc(1, Side::Buy, 100, 10))?.is_none());
+        assert!(book.add_order(gtc(2, Side::Sell, 101, 10))?.is_none());
+        assert!(book.add_order(gtc(3, Side::Buy, 100, 5))?.is_none());
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(100, 15)]);
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(101, 10)]);
+        assert!(book.get_trade_history().is_empty());
+
+        assert!(book.add_order(gtc(4, Side::Sell, 100, 1))?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_level_order_count() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 20))?;
+        book.add_order(gtc(3, Side::Buy, 100, 30))?;
+        book.add_order(gtc(4, Side::Buy, 99, 60))?;
+
+        let infos = book.get_order_infos();
+        let counts: Vec<(Price, usize)> = infos
+            .get_bids()
+            .iter()
+            .map(|level| (*level.get_price(), *level.get_order_count()))
+            .collect();
+        assert_eq!(counts, vec![(99, 1), (100, 3)]);
+        assert_eq!(*book.depth(1).get_bids()[0].get_order_count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let order = |order_id, quantity| {
+            Order::new(
+                OrderType::GoodTillCancel,
+                order_id,
+                Side::Buy,
+                100,
+                quantity,
+            )
+        };
+        book.submit(order(1, 10))?;
+        book.set_max_order_quantity(Some(50));
+
+        book.validate_order(&order(2, 10))?;
+        assert_eq!(book.open_order_ids(), vec![1]);
+
+        assert!(matches!(
+            book.validate_order(&order(1, 10)),
+            Err(OrderAlreadyExists(1))
+        ));
+        assert!(matches!(
+            book.submit(order(1, 10)),
+            Err(OrderAlreadyExists(1))
+        ));
+        assert!(matches!(
+            book.validate_order(&order(3, 51)),
+            Err(QuantityTooLarge { order_id: 3, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_of_book_listener() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+
+        let changes = Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = changes.clone();
+        book.set_top_of_book_listener(Some(Box::new(move |bid, ask| {
+            recorded.lock().unwrap().push((bid, ask))
+        })));
+
+        // new best bid
+        book.add_order(gtc(2, Side::Buy, 101, 10))?;
+        // behind the best, on the best level, or a cancel behind it
+        book.add_order(gtc(3, Side::Buy, 99, 10))?;
+        book.add_order(gtc(4, Side::Buy, 101, 10))?;
+        book.cancel_order(3)?;
+        assert_eq!(*changes.lock().unwrap(), vec![(Some(101), None)]);
+
+        // a modify is one change even though it removes and re-adds
+        book.add_order(gtc(5, Side::Sell, 103, 10))?;
+        book.modify_order(OrderModify::new(5, None, Some(102), None))?;
+        // trades leaving the best levels in place don't move the top of book
+        book.add_order(gtc(6, Side::Sell, 101, 5))?;
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                (Some(101), None),
+                (Some(101), Some(103)),
+                (Some(101), Some(102))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_liquidity_within() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 99, 20))?;
+        book.add_order(gtc(3, Side::Buy, 95, 30))?;
+        assert_eq!(book.liquidity_within(250.0), None);
+
+        book.add_order(gtc(4, Side::Sell, 102, 5))?;
+        book.add_order(gtc(5, Side::Sell, 103, 15))?;
+        book.add_order(gtc(6, Side::Sell, 110, 40))?;
+
+        // mid of 101, so the window is 98.475 to 103.525
+        assert_eq!(book.liquidity_within(250.0), Some((30, 20)));
+        assert_eq!(book.liquidity_within(0.0), Some((0, 0)));
+        assert_eq!(book.liquidity_within(1e9), Some((60, 60)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_noop_modify_keeps_queue_position() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 10))?;
+        book.add_order(gtc(3, Side::Sell, 100, 4))?;
+
+        assert_eq!(
+            book.modify_order(OrderModify::new(1, None, None, None))?,
+            ModifyResult::Unchanged
+        );
+        assert_eq!(
+            book.modify_order(OrderModify::new(1, Some(Side::Buy), Some(100), Some(10)))?,
+            ModifyResult::Unchanged
+        );
+        // order 1 keeps both its place and its partial fill
+        assert_eq!(
+            book.level_orders(Side::Buy, 100),
+            Some(vec![(1, 6), (2, 10)])
+        );
+
+        // 4 of the new total of 12 are already filled
+        book.modify_order(OrderModify::new(1, None, None, Some(12)))?;
+        assert_eq!(
+            book.level_orders(Side::Buy, 100),
+            Some(vec![(2, 10), (1, 8)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_peg_mid_follows_midpoint() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let peg = |order_id, side, quantity| {
+            Order::new(OrderType::PegMid, order_id, side, 0, quantity).to_order_ref()
+        };
+
+        // no midpoint yet, so the peg waits outside the levels
+        book.add_order(gtc(1, Side::Buy, 98, 10))?;
+        book.add_order(peg(10, Side::Buy, 5))?;
+        assert_eq!(book.best_bid(), Some(98));
+        assert_eq!(book.open_order_ids_side(Side::Buy).len(), 2);
+
+        book.add_order(gtc(2, Side::Sell, 104, 10))?;
+        assert_eq!(book.level_orders(Side::Buy, 101), Some(vec![(10, 5)]));
+
+        // a new best ask moves the midpoint to 100
+        book.add_order(gtc(3, Side::Sell, 102, 10))?;
+        assert_eq!(book.level_orders(Side::Buy, 101), None);
+        assert_eq!(book.level_orders(Side::Buy, 100), Some(vec![(10, 5)]));
+
+        // a midpoint between ticks rounds away from the other side
+        book.cancel_order(3)?;
+        book.add_order(gtc(4, Side::Sell, 103, 10))?;
+        book.add_order(peg(11, Side::Sell, 5))?;
+        assert_eq!(book.level_orders(Side::Buy, 100), Some(vec![(10, 5)]));
+        assert_eq!(book.level_orders(Side::Sell, 101), Some(vec![(11, 5)]));
+
+        // losing a side deactivates the pegs until it comes back
+        book.cancel_order(1)?;
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![]);
+        assert_eq!(
+            levels(book.get_order_infos().get_asks()),
+            vec![(103, 10), (104, 10)]
+        );
+        assert_eq!(book.open_order_ids_side(Side::Sell).len(), 3);
+
+        // both pegs land on a whole midpoint of 100 and trade with each other
+        book.add_order(gtc(5, Side::Buy, 97, 10))?;
+        let trade = book.get_trade_history().last().unwrap();
+        assert_eq!(trade.get_bid_trade().order_id, 10);
+        assert_eq!(trade.get_ask_trade().order_id, 11);
+        assert_eq!(*trade.get_price(), 100);
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(97, 10)]);
+        assert!(matches!(book.cancel_order(10), Err(OrderAlreadyFilled(10))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_orders() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 99, 10))?;
+        book.add_order(gtc(3, Side::Sell, 105, 10))?;
+
+        let (cancelled, not_found) = book.cancel_orders(&[7, 1, 3, 8, 1]);
+        assert_eq!(cancelled, vec![1, 3]);
+        assert_eq!(not_found, vec![7, 8, 1]);
+        assert_eq!(book.open_order_ids(), vec![2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expire_by_age() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let good_for_count = |order_id, count| {
+            Order::new(OrderType::GoodForCount(count), order_id, Side::Buy, 100, 10).to_order_ref()
+        };
+        book.add_order(good_for_count(1, 3))?;
+        book.add_order(good_for_count(2, 5))?;
+
+        // order 1 has seen orders 2 and 3 added after it
+        book.add_order(gtc(3, Side::Sell, 110, 10))?;
+        assert!(book.expire_by_age(*book.get_next_seq()).is_empty());
+
+        // a third later add expires it
+        book.add_order(gtc(4, Side::Sell, 111, 10))?;
+        assert_eq!(book.expire_by_age(*book.get_next_seq()), vec![1]);
+        let mut open_order_ids = book.open_order_ids();
+        open_order_ids.sort();
+        assert_eq!(open_order_ids, vec![2, 3, 4]);
+
+        // order 2 needs five later orders
+        assert!(book.expire_by_age(*book.get_next_seq() + 2).is_empty());
+        assert_eq!(book.expire_by_age(*book.get_next_seq() + 3), vec![2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_ascii_heatmap() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.to_ascii_heatmap(5), "");
+
+        book.add_order(gtc(1, Side::Buy, 10000, 40))?;
+        book.add_order(gtc(2, Side::Buy, 9990, 10))?;
+        book.add_order(gtc(3, Side::Buy, 9980, 20))?;
+        book.add_order(gtc(4, Side::Sell, 10010, 20))?;
+        book.add_order(gtc(5, Side::Sell, 10020, 30))?;
+
+        let bar = |width| "#".repeat(width);
+        assert_eq!(
+            book.to_ascii_heatmap(2),
+            [
+                format!("ASK   100.20       30 {}", bar(30)),
+                format!("ASK   100.10       20 {}", bar(20)),
+                format!("BID   100.00       40 {}", bar(40)),
+                format!("BID    99.90       10 {}", bar(10)),
+            ]
+            .map(|row| row + "\n")
+            .concat()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_queue_ahead_ratio() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 20))?;
+        book.add_order(gtc(3, Side::Buy, 100, 30))?;
+        book.add_order(gtc(4, Side::Buy, 99, 50))?;
+
+        assert_eq!(book.queue_ahead_ratio(1)?, 0.0);
+        assert_eq!(book.queue_ahead_ratio(2)?, 10.0 / 41.0);
+        assert_eq!(book.queue_ahead_ratio(3)?, 30.0 / 31.0);
+        // alone in its level
+        assert_eq!(book.queue_ahead_ratio(4)?, 0.0);
+        assert!(book.queue_ahead_ratio(1)? < book.queue_ahead_ratio(3)?);
+
+        assert!(matches!(book.queue_ahead_ratio(9), Err(OrderNotFound(9))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stp_modes() -> BookResult<()> {
+        let owned = |order_id, side, quantity, owner| {
+            Order::new(OrderType::GoodTillCancel, order_id, side, 100, quantity).with_owner(owner)
+        };
+        // (mode, quantity of order 2 the owner traded with itself, ids left open)
+        let cases = [
+            (StpMode::None, 10, vec![3]),
+            (StpMode::CancelResting, 0, vec![3]),
+            (StpMode::CancelNewest, 0, vec![2]),
+            (StpMode::CancelBoth, 0, vec![]),
+        ];
+        for (stp_mode, self_traded, open_order_ids) in cases {
+            let mut book = OrderBook::new("QQQ");
+            book.set_stp_mode(stp_mode);
+            book.submit(owned(1, Side::Sell, 5, 8))?;
+            book.submit(owned(2, Side::Sell, 10, 7))?;
+
+            let trades = book.submit(owned(3, Side::Buy, 20, 7))?.unwrap_or_default();
+
+            // the fill against another owner stands in every mode
+            assert_eq!(trades[0].get_ask_trade().order_id, 1, "{stp_mode:?}");
+            assert_eq!(*trades[0].get_quantity(), 5, "{stp_mode:?}");
+            let self_trades: Quantity = trades[1..].iter().map(|trade| *trade.get_quantity()).sum();
+            assert_eq!(self_trades, self_traded, "{stp_mode:?}");
+            assert_eq!(book.open_order_ids(), open_order_ids, "{stp_mode:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stp_ignores_orders_without_owner() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_stp_mode(StpMode::CancelBoth);
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        let trades = book
+            .add_order(gtc(2, Side::Buy, 100, 10))?
+            .unwrap_or_default();
+        assert_eq!(trades.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trade_listener() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 101, 10))?;
+
+        let fills = Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = fills.clone();
+        book.set_trade_listener(Some(Box::new(move |trade: TradeView| {
+            recorded.lock().unwrap().push((
+                trade.get_ask_trade().order_id,
+                *trade.get_price(),
+                *trade.get_quantity(),
+            ))
+        })));
+
+        book.add_order(gtc(3, Side::Buy, 101, 15))?;
+        assert_eq!(*fills.lock().unwrap(), vec![(1, 100, 10), (2, 101, 5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_price_improvement() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.price_improvement(Side::Buy, 120, 10), None);
+
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 110, 10))?;
+        book.add_order(gtc(3, Side::Sell, 130, 10))?;
+
+        // 20 per unit at 100
+        assert_eq!(book.price_improvement(Side::Buy, 120, 10), Some(200));
+        // plus 10 per unit at 110, nothing beyond the limit
+        assert_eq!(book.price_improvement(Side::Buy, 120, 30), Some(300));
+        assert_eq!(book.price_improvement(Side::Buy, 99, 10), None);
+        // filling at the limit is no improvement
+        assert_eq!(book.price_improvement(Side::Buy, 100, 10), Some(0));
+
+        book.add_order(gtc(4, Side::Buy, 90, 10))?;
+        assert_eq!(book.price_improvement(Side::Sell, 85, 4), Some(20));
+
+        // 4e9 per unit over 4e9 units is too large for an i64, let alone a Price
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, -2_000_000_000, 4_000_000_000))?;
+        assert_eq!(
+            book.price_improvement(Side::Buy, 2_000_000_000, 4_000_000_000),
+            None
+        );
+        assert_eq!(
+            book.price_improvement(Side::Buy, -1_999_999_990, 1),
+            Some(10)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_orders_match_only_on_resume() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.halt();
+        assert_eq!(book.get_phase(), &Phase::Halted);
+
+        assert!(book.add_order(gtc(2, Side::Buy, 101, 15))?.is_none());
+        assert!(book.add_order(gtc(3, Side::Sell, 99, 10))?.is_none());
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(101), Some(99)));
+        assert_eq!(book.traded_volume(), 0);
+
+        let trades = book.resume()?.unwrap();
+        assert_eq!(book.get_phase(), &Phase::Continuous);
+        assert_eq!(trades.len(), 2);
+        assert_eq!(book.traded_volume(), 15);
+        assert_eq!((book.best_bid(), book.best_ask()), (None, Some(100)));
+
+        // continuous again, so new crossing orders trade straight away
+        assert!(book.add_order(gtc(4, Side::Buy, 100, 5))?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remaining_quantity_mirror() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.add_order(
+            Order::new(OrderType::GoodTillCancel, 2, Side::Sell, 101, 30)
+                .with_reserve(10)
+                .to_order_ref(),
+        )?;
+        book.add_order(gtc(3, Side::Buy, 101, 14))?;
+        book.add_order(gtc(4, Side::Buy, 101, 10))?;
+
+        for (order_id, order_entry) in &book.track_orders {
+            let locked = *read_order(book.get_order_ref(order_id)?).get_remaining_quantity();
+            assert_eq!(
+                order_entry.remaining_quantity.load(Relaxed),
+                locked as u64,
+                "order {order_id}"
+            );
+        }
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(101, 6)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ladders() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+        book.add_order(gtc(2, Side::Buy, 101, 20))?;
+        book.add_order(gtc(3, Side::Buy, 100, 30))?;
+        book.add_order(gtc(4, Side::Buy, 101, 5))?;
+        book.add_order(gtc(5, Side::Sell, 104, 10))?;
+        book.add_order(gtc(6, Side::Sell, 102, 15))?;
+
+        assert_eq!(book.bid_ladder(), vec![(101, 25), (100, 30), (99, 10)]);
+        assert_eq!(book.ask_ladder(), vec![(102, 15), (104, 10)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_depth_weighted_mid() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        assert_eq!(book.depth_weighted_mid(2), None);
+
+        book.add_order(gtc(2, Side::Buy, 98, 30))?;
+        book.add_order(gtc(3, Side::Buy, 90, 100))?;
+        book.add_order(gtc(4, Side::Sell, 102, 30))?;
+        book.add_order(gtc(5, Side::Sell, 106, 10))?;
+
+        // bids (100 * 10 + 98 * 30) / 40 = 98.5, asks (102 * 30 + 106 * 10) / 40 = 103
+        assert_eq!(book.depth_weighted_mid(2), Some(100.75));
+        // top of book only, so the plain mid
+        assert_eq!(book.depth_weighted_mid(1), book.mid_price());
+        assert_eq!(book.depth_weighted_mid(0), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_orders_in_range() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        for (order_id, price) in [(1, 96), (2, 97), (3, 98), (4, 99), (5, 97), (6, 100)] {
+            book.add_order(gtc(order_id, Side::Buy, price, 10))?;
+        }
+
+        let order_ids = |orders: Vec<OrderRef>| -> Vec<OrderId> {
+            orders
+                .iter()
+                .map(|order| *read_order(order).get_order_id())
+                .collect()
+        };
+        assert_eq!(
+            order_ids(book.orders_in_range(Side::Buy, 97, 99)),
+            vec![2, 5, 3, 4]
+        );
+        assert!(book.orders_in_range(Side::Buy, 99, 97).is_empty());
+
+        assert_eq!(book.cancel_in_range(Side::Buy, 97, 99), vec![2, 5, 3, 4]);
+        assert_eq!(book.bid_ladder(), vec![(100, 10), (96, 10)]);
+        assert!(book.cancel_in_range(Side::Buy, 97, 99).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pre_trade_hook_veto() -> BookResult<()> {
+        let owned = |order_id, side, price, quantity, owner| {
+            Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity).with_owner(owner)
+        };
+        let mut book = OrderBook::new("QQQ");
+        // owner 9 has no credit with owner 7
+        book.set_pre_trade_hook(Some(Box::new(|aggressor: &Order, resting: &Order, _| {
+            !(*aggressor.get_owner() == 9 && *resting.get_owner() == 7)
+        })));
+        book.submit(owned(1, Side::Sell, 100, 10, 7))?;
+        book.submit(owned(2, Side::Sell, 100, 10, 8))?;
+        book.submit(owned(3, Side::Sell, 100, 10, 8))?;
+        book.submit(owned(4, Side::Sell, 101, 10, 8))?;
+
+        let trades = book.submit(owned(5, Side::Buy, 101, 25, 9))?.unwrap();
+        let fills: Vec<(OrderId, Quantity)> = trades
+            .iter()
+            .map(|trade| (trade.get_ask_trade().order_id, *trade.get_quantity()))
+            .collect();
+        assert_eq!(fills, vec![(2, 10), (3, 10), (4, 5)]);
+
+        // the vetoed order kept its place at the front of its level
+        assert_eq!(book.ask_ladder(), vec![(100, 10), (101, 5)]);
+        assert_eq!(book.queue_ahead_ratio(1)?, 0.0);
+
+        // other counterparties still trade with it
+        let trades = book.submit(owned(6, Side::Buy, 100, 4, 8))?.unwrap();
+        assert_eq!(trades[0].get_ask_trade().order_id, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_execution_price_rules() -> BookResult<()> {
+        let prices = |trades: Option<Trades>| -> Vec<Price> {
+            trades
+                .unwrap_or_default()
+                .iter()
+                .map(|trade| *trade.get_price())
+                .collect()
+        };
+        // (rule, auction prices)
+        let cases = [
+            (ExecutionPrice::RestingPrice, vec![110, 100]),
+            (ExecutionPrice::Midpoint, vec![105, 102]),
+            (ExecutionPrice::MidpointCappedByLimit, vec![105, 102]),
+        ];
+        for (execution_price, auction) in cases {
+            // the mid of 100 and 110 is above both resting bids, which fill at their own limits
+            let mut book = OrderBook::new("QQQ");
+            book.set_execution_price(execution_price);
+            book.add_order(gtc(1, Side::Buy, 100, 10))?;
+            book.add_order(gtc(2, Side::Buy, 90, 10))?;
+            book.add_order(gtc(3, Side::Sell, 110, 10))?;
+            let trades = book.add_order(gtc(4, Side::Sell, 90, 20))?;
+            assert_eq!(prices(trades), vec![100, 90], "{execution_price:?}");
+
+            // crossed during the auction, the mid of 110 and 100 is beyond bid 6's limit
+            let mut book = OrderBook::new("QQQ");
+            book.set_execution_price(execution_price);
+            book.set_phase(Phase::Auction)?;
+            book.add_order(gtc(4, Side::Buy, 110, 5))?;
+            book.add_order(gtc(5, Side::Sell, 100, 10))?;
+            book.add_order(gtc(6, Side::Buy, 102, 5))?;
+            assert_eq!(prices(book.uncross()?), auction, "{execution_price:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_level_count() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.level_count(Side::Buy), 0);
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 99, 10))?;
+        book.add_order(gtc(3, Side::Buy, 100, 10))?;
+        book.add_order(gtc(4, Side::Buy, 98, 10))?;
+        assert_eq!(book.level_count(Side::Buy), 3);
+        assert_eq!(book.level_count(Side::Sell), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_on_empty_opposite() -> BookResult<()> {
+        let fak = |order_id, side, price| {
+            Order::new(OrderType::FillAndKill, order_id, side, price, 10).to_order_ref()
+        };
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+
+        // off by default, the order is just dropped
+        assert!(book.add_order(fak(2, Side::Buy, 101))?.is_none());
+
+        book.set_reject_on_empty_opposite(true);
+        assert!(matches!(
+            book.add_order(fak(3, Side::Buy, 101)),
+            Err(NoLiquidity(3))
+        ));
+        assert_eq!(book.open_order_ids(), vec![1]);
+
+        // asks exist but don't cross, so this is no reject
+        book.add_order(gtc(4, Side::Sell, 105, 10))?;
+        assert!(book.add_order(fak(5, Side::Buy, 101))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_order_agrees_with_add_order_on_empty_opposite() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_reject_on_empty_opposite(true);
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+
+        let fak = Order::new(OrderType::FillAndKill, 2, Side::Buy, 101, 10);
+        assert!(matches!(book.validate_order(&fak), Err(NoLiquidity(2))));
+        assert!(matches!(
+            book.add_order(fak.to_order_ref()),
+            Err(NoLiquidity(2))
+        ));
+
+        book.add_order(gtc(3, Side::Sell, 101, 5))?;
+        let fak = Order::new(OrderType::FillAndKill, 4, Side::Buy, 101, 10);
+        book.validate_order(&fak)?;
+        assert!(book.add_order(fak.to_order_ref())?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_gross_notional() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.gross_notional(Side::Buy), 0);
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 5))?;
+        book.add_order(gtc(3, Side::Buy, 95, 20))?;
+        book.add_order(gtc(4, Side::Sell, Price::MAX, Quantity::MAX))?;
+        book.add_order(gtc(6, Side::Sell, Price::MAX, Quantity::MAX))?;
+        // partially fills order 1
+        book.add_order(gtc(5, Side::Sell, 100, 4))?;
+
+        assert_eq!(book.gross_notional(Side::Buy), 100 * 11 + 95 * 20);
+        assert_eq!(
+            book.gross_notional(Side::Sell),
+            2 * Price::MAX as i128 * Quantity::MAX as i128
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_midpoint_execution_lands_on_tick() -> BookResult<()> {
+        // (tick size, price against a resting ask, price against a resting bid)
+        for (tick_size, ask_price, bid_price) in [(1, 10003, 10002), (5, 10005, 10000)] {
+            for resting_side in [Side::Sell, Side::Buy] {
+                let mut book = OrderBook::new("QQQ");
+                book.set_tick_size(tick_size)?;
+                book.set_execution_price(ExecutionPrice::Midpoint);
+
+                // crossed during the auction, so the midpoint of 10002.5 is within both limits
+                book.set_phase(Phase::Auction)?;
+                let (resting_price, aggressor_price) = match resting_side {
+                    Side::Buy => (10005, 10000),
+                    Side::Sell => (10000, 10005),
+                };
+                book.add_order(gtc(1, resting_side, resting_price, 10))?;
+                book.add_order(gtc(2, resting_side.opposite(), aggressor_price, 10))?;
+
+                // rounded in the resting side's favour
+                let trades = book.uncross()?.unwrap();
+                let expected = match resting_side {
+                    Side::Buy => bid_price,
+                    Side::Sell => ask_price,
+                };
+                assert_eq!(
+                    *trades[0].get_price(),
+                    expected,
+                    "tick {tick_size}, resting {resting_side:?}"
+                );
+                assert!((10000..=10005).contains(trades[0].get_price()));
+            }
+        }
+
+        let mut book = OrderBook::new("QQQ");
+        assert!(matches!(book.set_tick_size(0), Err(InvalidPrice(_))));
+        assert_eq!(book.get_tick_size(), &1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_capacity() -> BookResult<()> {
+        let mut book = OrderBook::with_capacity("QQQ", 64, 10_000);
+        assert!(book.track_orders.capacity() >= 10_000);
+        assert_eq!(book.get_asset(), "QQQ");
+
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        let trades = book.add_order(gtc(2, Side::Sell, 100, 4))?.unwrap();
+        assert_eq!(*trades[0].get_quantity(), 4);
+        assert_eq!(book.bid_ladder(), vec![(100, 6)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_locked() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        assert!(!book.is_locked());
+
+        book.halt();
+        book.add_order(gtc(2, Side::Sell, 100, 10))?;
+        assert!(book.is_locked());
+
+        // crossed rather than locked
+        book.add_order(gtc(3, Side::Sell, 99, 10))?;
+        assert!(!book.is_locked());
+
+        book.resume()?;
+        assert!(!book.is_locked());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_limited_owner_recovers() -> BookResult<()> {
+        use crate::throttle::ManualClock;
+        use std::time::Duration;
+
+        let owned = |order_id, price, owner| {
+            Order::new(OrderType::GoodTillCancel, order_id, Side::Buy, price, 10).with_owner(owner)
+        };
+        let clock = ManualClock::new();
+        let mut book = OrderBook::new("QQQ");
+        book.set_rate_limiter(Some(RateLimiter::new(
+            3,
+            Duration::from_secs(1),
+            Box::new(clock.clone()),
+        )));
+
+        book.submit(owned(1, 100, 7))?;
+        book.submit(owned(2, 99, 7))?;
+        book.modify_order(OrderModify::new(1, None, Some(98), None))?;
+        assert!(matches!(
+            book.submit(owned(3, 97, 7)),
+            Err(RateLimited { owner: 7 })
+        ));
+        assert!(matches!(
+            book.modify_order(OrderModify::new(2, None, Some(96), None)),
+            Err(RateLimited { owner: 7 })
+        ));
+        // other owners have their own allowance
+        book.submit(owned(4, 95, 8))?;
+
+        clock.advance(Duration::from_secs(1));
+        book.submit(owned(3, 97, 7))?;
+        assert_eq!(book.bid_ladder().len(), 4);
+        Ok(())
+    }
 
-    pub fn get_order_infos(&self) -> OrderBookLevelInfos {
-        // grab price, quantity
-        // for every price level, sum up all order quantities
-        let bids: LevelInfos = self
-            .bid_side
-            .iter() // price level
-            .map(|(price, bids)| {
-                let quantity: Quantity = bids
-                    .iter()
-                    .map(|(_, order)| *order.lock().unwrap().get_remaining_quantity())
-                    .sum();
-                return LevelInfo {
-                    price: *price,
-                    quantity,
-                };
-            })
-            .collect();
+    #[test]
+    fn test_residual_after() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 60))?;
+        book.add_order(gtc(2, Side::Sell, 101, 40))?;
+        book.add_order(gtc(3, Side::Sell, 105, 500))?;
 
-        let asks: LevelInfos = self
-            .ask_side
-            .iter() // price level
-            .map(|(price, asks)| {
-                let quantity: Quantity = asks
-                    .iter()
-                    .map(|(_, order)| *order.lock().unwrap().get_remaining_quantity())
-                    .sum();
-                return LevelInfo {
-                    price: *price,
-                    quantity,
-                };
-            })
-            .collect();
+        let buy =
+            |price, quantity| Order::new(OrderType::GoodTillCancel, 4, Side::Buy, price, quantity);
+        assert_eq!(book.residual_after(&buy(101, 150)), 50);
+        assert_eq!(book.residual_after(&buy(101, 80)), 0);
+        assert_eq!(book.residual_after(&buy(99, 150)), 150);
+        // nothing changed in the book
+        assert_eq!(book.ask_ladder(), vec![(100, 60), (101, 40), (105, 500)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_oldest_and_newest_at() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 100, 20))?;
+        book.add_order(gtc(3, Side::Sell, 100, 30))?;
+
+        assert_eq!(book.cancel_oldest_at(Side::Sell, 100), Some(1));
+        assert_eq!(book.cancel_newest_at(Side::Sell, 100), Some(3));
+        assert_eq!(book.ask_ladder(), vec![(100, 20)]);
 
-        OrderBookLevelInfos { bids, asks }
+        assert_eq!(book.cancel_newest_at(Side::Sell, 100), Some(2));
+        assert_eq!(book.cancel_oldest_at(Side::Sell, 100), None);
+        assert_eq!(book.cancel_oldest_at(Side::Buy, 100), None);
+        Ok(())
     }
 
-    /// Checks whether order can be matched given book's current state.
-    fn can_match(&self, side: &Side, price: &Price) -> bool {
-        match side {
-            Side::Buy => {
-                self.ask_side
-                    .iter()
-                    .next() // None if asks empty
-                    .map_or(false, |(best_ask_price, _)| price >= best_ask_price)
+    #[test]
+    fn test_round_trip_cost() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+        book.add_order(gtc(2, Side::Buy, 98, 10))?;
+        book.add_order(gtc(3, Side::Sell, 101, 10))?;
+        book.add_order(gtc(4, Side::Sell, 102, 10))?;
+
+        // top of book spread of 2
+        assert_eq!(book.round_trip_cost(10), Some(20.0));
+        // average prices of 101.5 and 98.5
+        assert_eq!(book.round_trip_cost(20), Some(60.0));
+        assert_eq!(book.round_trip_cost(21), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_below_filled_quantity_cancels() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 100))?;
+        book.add_order(gtc(2, Side::Sell, 100, 40))?;
+
+        // a new total of 30 is less than the 40 already filled
+        assert_eq!(
+            book.modify_order(OrderModify::new(1, None, None, Some(30)))?,
+            ModifyResult::Cancelled
+        );
+        assert!(matches!(
+            book.get_order_ref(&1),
+            Err(OrderBookError::OrderNotFound(1))
+        ));
+        assert_eq!(book.level_orders(Side::Buy, 100), None);
+        assert!(book.open_order_ids().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resting_age() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.submit(
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 30).with_reserve(10),
+        )?;
+        assert_eq!(book.resting_age(1, *book.get_next_seq())?, 1);
+
+        book.add_order(gtc(2, Side::Sell, 101, 10))?;
+        book.add_order(gtc(3, Side::Buy, 99, 10))?;
+        assert_eq!(book.resting_age(1, *book.get_next_seq())?, 3);
+        assert_eq!(book.resting_age(3, *book.get_next_seq())?, 1);
+
+        // refreshing the reserve sends order 1 to the back of the level, but it keeps resting
+        book.add_order(gtc(4, Side::Buy, 101, 10))?;
+        assert_eq!(
+            book.level_orders(Side::Sell, 101),
+            Some(vec![(2, 10), (1, 10)])
+        );
+        assert_eq!(book.resting_age(1, *book.get_next_seq() + 2)?, 7);
+
+        assert!(matches!(
+            book.resting_age(4, *book.get_next_seq()),
+            Err(OrderBookError::OrderAlreadyFilled(4))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_queue_priority() -> BookResult<()> {
+        // (new price, new quantity, order 1's level afterwards)
+        let cases = [
+            (100, 6, vec![(1, 6), (2, 10)]),
+            (100, 14, vec![(2, 10), (1, 14)]),
+            (99, 6, vec![(3, 10), (1, 6)]),
+            (99, 14, vec![(3, 10), (1, 14)]),
+        ];
+        for (price, quantity, level) in cases {
+            let mut book = OrderBook::new("QQQ");
+            book.set_event_recording(true);
+            book.add_order(gtc(1, Side::Buy, 100, 10))?;
+            book.add_order(gtc(2, Side::Buy, 100, 10))?;
+            book.add_order(gtc(3, Side::Buy, 99, 10))?;
+
+            book.modify_order(OrderModify::new(1, None, Some(price), Some(quantity)))?;
+            assert_eq!(
+                book.level_orders(Side::Buy, price),
+                Some(level),
+                "{price} {quantity}"
+            );
+
+            let mut reconstructor = crate::events::DepthReconstructor::new();
+            for event in book.drain_events() {
+                reconstructor.apply(&event);
             }
-            Side::Sell => self
-                .bid_side
-                .iter()
-                .next()
-                .map_or(false, |(best_bid_price, _)| price <= best_bid_price),
+            assert_eq!(reconstructor.get_order_infos(), book.get_order_infos());
         }
+        Ok(())
     }
 
-    /// Match bids and asks.
-    /// Returns None if no matches are currently possible.
-    ///
-    /// # Errors:
-    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
-    fn match_orders(&mut self) -> BookResult<Option<Trades>> {
-        let mut trades: Vec<Trade> = vec![];
-        trades.reserve(self.track_orders.len());
+    #[test]
+    fn test_reduce_reserve_order_takes_hidden_first() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.submit(
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 30).with_reserve(10),
+        )?;
+        book.add_order(gtc(2, Side::Sell, 101, 10))?;
 
-        // loops as long as there are orders to match
-        loop {
-            // if either bids or asks empty, no matches possible
-            if self.ask_side.is_empty() || self.bid_side.is_empty() {
-                break;
-            }
+        book.modify_order(OrderModify::new(1, None, None, Some(15)))?;
+        let order = read_order(book.get_order_ref(&1)?).clone();
+        assert_eq!(*order.get_remaining_quantity(), 10);
+        assert_eq!(*order.get_hidden_quantity(), 5);
+        assert_eq!(
+            book.level_orders(Side::Sell, 101),
+            Some(vec![(1, 10), (2, 10)])
+        );
 
-            let (best_bid_price, mut bids) = match self.bid_side.pop_last() {
-                Some((b_b_p, bids)) => (b_b_p, bids),
-                None => break,
-            };
+        book.modify_order(OrderModify::new(1, None, None, Some(4)))?;
+        assert_eq!(
+            book.level_orders(Side::Sell, 101),
+            Some(vec![(1, 4), (2, 10)])
+        );
+        Ok(())
+    }
 
-            // self.ask_side.pop_first().map_or(break, |(bap, asks)| (bap, asks));
-            let (best_ask_price, mut asks) = match self.ask_side.pop_first() {
-                Some((b_a_p, asks)) => (b_a_p, asks),
-                None => break,
-            };
+    #[test]
+    fn test_order_fills() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 102, 30))?;
+        book.add_order(gtc(2, Side::Sell, 100, 5))?;
+        book.add_order(gtc(3, Side::Sell, 101, 10))?;
+        book.add_order(gtc(4, Side::Sell, 102, 20))?;
 
-            if best_bid_price < best_ask_price {
-                // no matches possible
-                break;
+        let fills = book.order_fills(1);
+        assert_eq!(
+            fills
+                .iter()
+                .map(|fill| (fill.price, fill.quantity))
+                .collect::<Vec<_>>(),
+            vec![(102, 5), (102, 10), (102, 15)]
+        );
+        assert_eq!(fills.iter().map(|fill| fill.quantity).sum::<Quantity>(), 30);
+        assert_eq!(book.order_fills(4).len(), 1);
+        assert!(book.order_fills(5).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_fills_per_match() -> BookResult<()> {
+        let sweep = |max_fills_per_match| -> BookResult<(Vec<Trades>, Vec<Trade>)> {
+            let mut book = OrderBook::new("QQQ");
+            book.set_max_fills_per_match(max_fills_per_match);
+            for order_id in 1..=10 {
+                book.add_order(gtc(order_id, Side::Sell, 100 + order_id as Price, 10))?;
             }
+            let aggressor = gtc(11, Side::Buy, 108, 80);
 
-            // match best bids with best asks
-            while bids.len() != 0 && asks.len() != 0 {
-                let mut bid = match bids.front() {
-                    Some((_, bid)) => bid.lock().unwrap(),
-                    None => break, // unreachable
-                };
+            let mut passes = vec![book.add_order(aggressor)?.unwrap_or_default()];
+            if max_fills_per_match.is_some() {
+                // the aggressor rests, crossing the book, until its sweep completes
+                assert_eq!(book.best_bid(), Some(108));
+                assert_eq!(book.best_ask(), Some(104));
+            }
+            while let Some(trades) = book.continue_matching()? {
+                passes.push(trades);
+            }
+            assert_eq!(book.best_bid(), None);
+            assert_eq!(book.best_ask(), Some(109));
+            Ok((passes, book.get_trade_history().clone()))
+        };
 
-                let mut ask = match asks.front() {
-                    Some((_, ask)) => ask.lock().unwrap(),
-                    None => break, // unreachable
-                };
+        let (passes, unbounded) = sweep(None)?;
+        assert_eq!(passes.len(), 1);
+        assert_eq!(unbounded.len(), 8);
 
-                let fill_quantity =
-                    min(*bid.get_remaining_quantity(), *ask.get_remaining_quantity());
-
-                bid.fill(fill_quantity)?;
-                ask.fill(fill_quantity)?;
-
-                let trade = Trade::new(
-                    TradeInfo {
-                        order_id: *bid.get_order_id(),
-                        price: *bid.get_price(),
-                        quantity: fill_quantity,
-                    },
-                    TradeInfo {
-                        order_id: *ask.get_order_id(),
-                        price: *ask.get_price(),
-                        quantity: fill_quantity,
-                    },
-                );
+        let (passes, bounded) = sweep(Some(3))?;
+        assert_eq!(
+            passes.iter().map(|trades| trades.len()).collect::<Vec<_>>(),
+            vec![3, 3, 2]
+        );
+        assert_eq!(passes.concat(), unbounded);
+        assert_eq!(bounded, unbounded);
+        Ok(())
+    }
 
-                println!("{:?}", trade);
+    #[test]
+    fn test_queries_on_one_sided_books() -> BookResult<()> {
+        let mut bids_only = OrderBook::new("QQQ");
+        bids_only.add_order(gtc(1, Side::Buy, 100, 10))?;
+        bids_only.add_order(gtc(2, Side::Buy, 99, 20))?;
+        // a buy into a book without asks just rests, a FaK is dropped
+        assert!(bids_only.add_order(gtc(3, Side::Buy, 101, 5))?.is_none());
+        assert!(bids_only
+            .submit(Order::new(OrderType::FillAndKill, 4, Side::Buy, 105, 5))?
+            .is_none());
 
-                trades.push(trade);
+        let mut asks_only = OrderBook::new("QQQ");
+        asks_only.add_order(gtc(1, Side::Sell, 101, 10))?;
+        asks_only.add_order(gtc(2, Side::Sell, 102, 20))?;
+        assert!(asks_only.add_order(gtc(3, Side::Sell, 100, 5))?.is_none());
+        assert!(asks_only
+            .submit(Order::new(OrderType::FillAndKill, 4, Side::Sell, 95, 5))?
+            .is_none());
 
-                if bid.is_filled() {
-                    mem::drop(bid);
-                    bids.pop_front();
-                }
+        let mut empty = OrderBook::new("QQQ");
+        assert!(empty.continue_matching()?.is_none());
 
-                if ask.is_filled() {
-                    mem::drop(ask);
-                    asks.pop_front();
-                }
+        for (book, resting_side) in [
+            (&bids_only, Some(Side::Buy)),
+            (&asks_only, Some(Side::Sell)),
+            (&empty, None),
+        ] {
+            // queries needing both sides
+            assert_eq!(book.spread(), None);
+            assert!(!book.is_locked());
+            assert_eq!(book.mid_price(), None);
+            assert_eq!(book.spread_bps(), None);
+            assert_eq!(book.liquidity_within(100.0), None);
+            assert_eq!(book.microprice(), None);
+            assert_eq!(book.depth_weighted_mid(5), None);
+            assert_eq!(book.imbalance(5), None);
+            for side in [Side::Buy, Side::Sell] {
+                assert_eq!(book.effective_spread(side, 5), None);
             }
+            assert_eq!(book.round_trip_cost(5), None);
 
-            if bids.is_empty() {
-                self.bid_side.remove(&best_bid_price);
-            } else {
-                self.bid_side.insert(best_bid_price, bids);
-            }
+            for side in [Side::Buy, Side::Sell] {
+                let resting = resting_side == Some(side);
+                assert_eq!(book.best_price(side).is_some(), resting);
+                assert_eq!(book.level_count(side), if resting { 3 } else { 0 });
+                assert_eq!(book.resting_vwap(side).is_some(), resting);
+                assert_eq!(book.gross_notional(side) > 0, resting);
+                assert_eq!(
+                    book.open_order_ids_side(side).len(),
+                    if resting { 3 } else { 0 }
+                );
 
-            if asks.is_empty() {
-                self.ask_side.remove(&best_ask_price);
-            } else {
-                self.ask_side.insert(best_ask_price, asks);
+                // an aggressor on `side` finds nothing unless the other side rests
+                let opposite_resting = resting_side == Some(side.opposite());
+                let sweep = book.simulate_sweep(side, 5);
+                assert_eq!(sweep.vwap().is_some(), opposite_resting);
+                assert_eq!(
+                    *sweep.get_unfilled_quantity(),
+                    if opposite_resting { 0 } else { 5 }
+                );
+                assert_eq!(book.next_match(side).is_some(), opposite_resting);
+                assert_eq!(book.can_match(&side, &100), opposite_resting);
+                assert_eq!(
+                    book.price_improvement(side, 100, 5).is_some(),
+                    opposite_resting
+                );
+                let order = Order::new(OrderType::GoodTillCancel, 9, side, 100, 5);
+                assert_eq!(
+                    book.residual_after(&order),
+                    if opposite_resting { 0 } else { 5 }
+                );
             }
+            assert_eq!(book.best_bid(), book.best_price(Side::Buy));
+            assert_eq!(book.best_ask(), book.best_price(Side::Sell));
+            assert_eq!(
+                book.bid_ladder().len() + book.ask_ladder().len(),
+                book.open_order_ids().len()
+            );
+            assert_eq!(
+                book.to_ascii_heatmap(5).lines().count(),
+                book.open_order_ids().len()
+            );
+            assert!(book.get_trade_history().is_empty());
+            assert!(book.fill_report().is_empty());
+            assert!(book.volume_by_price().is_empty());
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fak_remainder_cancelled_after_match() -> BookResult<()> {
+        for side in [Side::Buy, Side::Sell] {
+            let mut book = OrderBook::new("QQQ");
+            let behind = match side {
+                Side::Buy => 90,
+                Side::Sell => 110,
+            };
+            book.add_order(gtc(1, side.opposite(), 100, 4))?;
+            book.add_order(gtc(2, side, behind, 5))?;
 
-        if !self.bid_side.is_empty() {
-            // ok for below to fail
-            let _ = self.prune_fak_from_order_book(Side::Buy);
+            let trades = book.submit(Order::new(OrderType::FillAndKill, 3, side, 100, 10))?;
+            assert_eq!(trades.map(|trades| trades.len()), Some(1));
+            assert_eq!(book.open_order_ids(), vec![2]);
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fak_remainder_behind_requeued_reserve() -> BookResult<()> {
+        for side in [Side::Buy, Side::Sell] {
+            let mut book = OrderBook::new("QQQ");
+            book.set_phase(Phase::Auction)?;
+            book.add_order(gtc(1, side.opposite(), 100, 8))?;
+            book.submit(Order::new(OrderType::GoodTillCancel, 2, side, 100, 20).with_reserve(5))?;
+            book.submit(Order::new(OrderType::FillAndKill, 3, side, 100, 10))?;
 
-        if !self.ask_side.is_empty() {
-            let _ = self.prune_fak_from_order_book(Side::Sell);
+            // order 2's refresh requeues it behind the FaK, which must still be cancelled
+            let trades = book.set_phase(Phase::Continuous)?.unwrap_or_default();
+            assert_eq!(trades.len(), 2);
+            assert_eq!(book.open_order_ids(), vec![2]);
+            assert_eq!(book.level_orders(side, 100), Some(vec![(2, 5)]));
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cumulative_depth() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Buy, 98, 5))?;
+        book.add_order(gtc(3, Side::Buy, 100, 15))?;
+        book.add_order(gtc(4, Side::Sell, 101, 7))?;
+        book.add_order(gtc(5, Side::Sell, 103, 3))?;
+        book.add_order(gtc(6, Side::Sell, 102, Quantity::MAX))?;
 
-        match trades.is_empty() {
-            true => Ok(None),
-            false => Ok(Some(trades)),
+        assert_eq!(book.cumulative_depth(Side::Buy), vec![(100, 25), (98, 30)]);
+        assert_eq!(
+            book.cumulative_depth(Side::Sell),
+            vec![(101, 7), (102, Quantity::MAX), (103, Quantity::MAX)]
+        );
+        for side in [Side::Buy, Side::Sell] {
+            let depth = book.cumulative_depth(side);
+            assert!(depth.windows(2).all(|pair| {
+                pair[0].1 <= pair[1].1 && is_better_price(side, pair[0].0, pair[1].0)
+            }));
         }
+        assert!(OrderBook::new("QQQ").cumulative_depth(Side::Buy).is_empty());
+        Ok(())
     }
 
-    fn prune_fak_from_order_book(&mut self, side: Side) -> BookResult<()> {
-        let orders = match side {
-            Side::Buy => self
-                .bid_side
-                .iter()
-                .next()
-                // need to create new err type, or make order_id optional?
-                .map_or(Err(BookSideEmpty(side)), |(_, orders)| Ok(orders)),
-            Side::Sell => self
-                .ask_side
-                .iter()
-                .rev()
-                .next()
-                .map_or(Err(BookSideEmpty(side)), |(_, orders)| Ok(orders)),
-        }?;
-
-        let fak_order_id: Option<OrderId> = {
-            let order = orders
-                .back()
-                .map_or(Err(BookSideEmpty(side)), |(_, order)| Ok(order))?
-                .lock()
-                .unwrap();
-            match order.get_order_type() {
-                OrderType::FillAndKill => Some(*order.get_order_id()),
-                _ => None,
-            }
+    #[test]
+    fn test_tags_carried_onto_fills() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.submit(
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 100, 20)
+                .with_reserve(5)
+                .with_tag("mm-quotes"),
+        )?;
+        // a modify keeps the tag
+        book.modify_order(OrderModify::new(1, None, Some(101), None))?;
+        let trades = book
+            .submit(Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 101, 12).with_tag("twap"))?
+            .unwrap_or_default();
+
+        assert_eq!(trades.len(), 3);
+        for trade in &trades {
+            assert_eq!(trade.get_ask_trade().tag.as_deref(), Some("mm-quotes"));
+            assert_eq!(trade.get_bid_trade().tag.as_deref(), Some("twap"));
+        }
+        assert!(book
+            .order_fills(1)
+            .iter()
+            .all(|fill| fill.tag.as_deref() == Some("mm-quotes")));
+
+        book.add_order(gtc(3, Side::Sell, 101, 5))?;
+        let trades = book.submit(Order::new(OrderType::FillAndKill, 4, Side::Buy, 101, 5))?;
+        assert!(trades
+            .unwrap_or_default()
+            .iter()
+            .all(|trade| trade.get_bid_trade().tag.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum() -> BookResult<()> {
+        let build = || -> BookResult<OrderBook> {
+            let mut book = OrderBook::new("QQQ");
+            book.add_order(gtc(1, Side::Buy, 100, 40))?;
+            book.add_order(gtc(2, Side::Buy, 99, 10))?;
+            book.add_order(gtc(3, Side::Sell, 101, 30))?;
+            book.add_order(gtc(4, Side::Sell, 105, 5))?;
+            Ok(book)
+        };
+        let book = build()?;
+
+        // same levels reached through different orders
+        let mut other = OrderBook::new("QQQ");
+        other.add_order(gtc(10, Side::Sell, 105, 5))?;
+        other.add_order(gtc(11, Side::Buy, 100, 15))?;
+        other.add_order(gtc(12, Side::Buy, 100, 25))?;
+        other.add_order(gtc(13, Side::Sell, 101, 30))?;
+        other.add_order(gtc(14, Side::Buy, 99, 10))?;
+        assert_eq!(book.checksum(10), other.checksum(10));
+        assert_eq!(
+            book.checksum(10),
+            crc32fast::hash(b"100:40,99:10|101:30,105:5")
+        );
+
+        // differences beyond the levels covered don't count
+        other.cancel_order(10)?;
+        assert_eq!(book.checksum(1), other.checksum(1));
+        assert_ne!(book.checksum(2), other.checksum(2));
+
+        let mut modified = build()?;
+        modified.modify_order(OrderModify::new(2, None, None, Some(9)))?;
+        assert_ne!(book.checksum(10), modified.checksum(10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sticky_levels() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_sticky_levels(true);
+        book.add_order(gtc(1, Side::Buy, 99, 10))?;
+        book.add_order(gtc(2, Side::Buy, 100, 10))?;
+        book.add_order(gtc(3, Side::Sell, 101, 5))?;
+        book.add_order(gtc(4, Side::Sell, 102, 5))?;
+
+        // emptied by a cancel and by a trade
+        book.cancel_order(1)?;
+        book.add_order(gtc(5, Side::Sell, 100, 10))?;
+        let infos = book.get_order_infos();
+        assert_eq!(levels(infos.get_bids()), vec![(99, 0), (100, 0)]);
+        assert_eq!(infos.get_bids()[0].get_order_count(), &0);
+        assert_eq!(levels(infos.get_asks()), vec![(101, 5), (102, 5)]);
+        assert_eq!(book.best_bid(), None);
+
+        // matching skips the zero quantity levels, and the filled aggressor was never shown
+        book.add_order(gtc(6, Side::Buy, 102, 7))?;
+        assert_eq!(
+            levels(book.get_order_infos().get_bids()),
+            vec![(99, 0), (100, 0)]
+        );
+        assert_eq!(
+            levels(book.get_order_infos().get_asks()),
+            vec![(101, 0), (102, 3)]
+        );
+
+        book.prune_empty_levels();
+        let infos = book.get_order_infos();
+        assert!(infos.get_bids().is_empty());
+        assert_eq!(levels(infos.get_asks()), vec![(102, 3)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_realized_spread() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let owned = |order_id, side, price, quantity| {
+            Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity).with_owner(7)
         };
 
-        if let Some(order_id) = fak_order_id {
-            self.cancel_order(order_id)?;
+        book.submit(owned(1, Side::Buy, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 100, 10))?;
+        assert_eq!(book.realized_spread(7), None);
+
+        book.submit(owned(3, Side::Sell, 101, 10))?;
+        book.add_order(gtc(4, Side::Buy, 101, 10))?;
+        assert_eq!(book.realized_spread(7), Some(1.0));
+        assert_eq!(book.realized_spread(8), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_withdraw() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.submit(
+            Order::new(OrderType::GoodTillCancel, 1, Side::Sell, 101, 30)
+                .with_reserve(10)
+                .with_owner(7),
+        )?;
+        book.add_order(gtc(2, Side::Buy, 99, 20))?;
+        book.add_order(gtc(3, Side::Buy, 98, 5))?;
+        book.add_order(gtc(4, Side::Buy, 101, 4))?;
+        let resting: Vec<Order> = [2, 1]
+            .iter()
+            .map(|order_id| Ok(read_order(book.get_order_ref(order_id)?).clone()))
+            .collect::<BookResult<_>>()?;
+
+        let withdrawn = book.withdraw(&[2, 5, 1]);
+        assert_eq!(withdrawn, resting);
+        assert_eq!(*withdrawn[1].get_remaining_quantity(), 6);
+        assert_eq!(*withdrawn[1].get_hidden_quantity(), 20);
+        assert_eq!(book.open_order_ids(), vec![3]);
+
+        // repriced and resubmitted
+        for order in withdrawn {
+            let price = *order.get_price() + 1;
+            let modified = OrderModify::new(*order.get_order_id(), None, Some(price), None);
+            book.submit(modified.to_order(order)?)?;
         }
+        assert_eq!(book.level_orders(Side::Sell, 102), Some(vec![(1, 10)]));
+        assert_eq!(book.level_orders(Side::Buy, 100), Some(vec![(2, 20)]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reduce_only() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_event_recording(true);
+        let owned = |order_id, side, price, quantity| {
+            Order::new(OrderType::GoodTillCancel, order_id, side, price, quantity).with_owner(7)
+        };
+
+        // owner 7 gets long 50
+        book.add_order(gtc(1, Side::Sell, 100, 50))?;
+        book.submit(owned(2, Side::Buy, 100, 50))?;
+        assert_eq!(book.position(7), 50);
+
+        book.add_order(gtc(3, Side::Buy, 99, 30))?;
+        book.add_order(gtc(4, Side::Buy, 98, 100))?;
+        let trades = book
+            .submit(owned(5, Side::Sell, 98, 80).with_reduce_only())?
+            .unwrap_or_default();
+        assert_eq!(
+            trades
+                .iter()
+                .map(|trade| *trade.get_quantity())
+                .collect::<Vec<_>>(),
+            vec![30, 20]
+        );
+        assert_eq!(book.position(7), 0);
+        assert_eq!(book.level_orders(Side::Buy, 98), Some(vec![(4, 80)]));
+        assert!(book.best_ask().is_none());
+
+        // nothing left to reduce, so a resting reduce only order is cancelled on contact
+        book.submit(owned(6, Side::Buy, 97, 10).with_reduce_only())?;
+        book.add_order(gtc(7, Side::Sell, 97, 90))?;
+        assert!(book.open_order_ids_side(Side::Buy).is_empty());
+        assert_eq!(book.level_orders(Side::Sell, 97), Some(vec![(7, 10)]));
+        assert_eq!(book.position(7), 0);
 
+        let mut reconstructor = crate::events::DepthReconstructor::new();
+        for event in book.drain_events() {
+            reconstructor.apply(&event);
+        }
+        assert_eq!(reconstructor.get_order_infos(), book.get_order_infos());
         Ok(())
     }
 
-    /// Get shared reference to an order within book given its id.
-    ///
-    /// # Errors:
-    /// - Returns [`OrderNotFound`](crate::error::OrderBookError)
-    fn get_order_ref(&self, order_id: &OrderId) -> BookResult<&OrderRef> {
-        let order_entry = self
-            .track_orders
-            .get(&order_id)
-            .ok_or(OrderNotFound(*order_id))?;
+    #[test]
+    fn test_orders_touched() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        for order_id in 1..=20 {
+            book.add_order(gtc(order_id, Side::Sell, 100, 3))?;
+        }
+        book.add_order(gtc(21, Side::Sell, 101, 50))?;
 
-        let book_side = match order_entry.book_side {
-            Side::Buy => &self.bid_side,
-            Side::Sell => &self.ask_side,
+        assert_eq!(book.orders_touched(Side::Buy, 0), 0);
+        assert_eq!(book.orders_touched(Side::Buy, 3), 1);
+        assert_eq!(book.orders_touched(Side::Buy, 10), 4);
+        assert_eq!(book.orders_touched(Side::Buy, 60), 20);
+        assert_eq!(book.orders_touched(Side::Buy, 61), 21);
+        assert_eq!(book.orders_touched(Side::Buy, 1000), 21);
+        assert_eq!(book.orders_touched(Side::Sell, 10), 0);
+
+        // the sweep agrees with what matching does
+        let trades = book
+            .add_order(gtc(22, Side::Buy, 101, 10))?
+            .unwrap_or_default();
+        assert_eq!(trades.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_order_with_result() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        book.add_order(gtc(2, Side::Sell, 101, 30))?;
+        book.add_order(gtc(3, Side::Sell, 103, 5))?;
+
+        let result = book.add_order_with_result(gtc(4, Side::Buy, 101, 50))?;
+        assert_eq!(result.aggressor_filled, 40);
+        assert_eq!(
+            result.aggressor_vwap,
+            Some((100.0 * 10.0 + 101.0 * 30.0) / 40.0)
+        );
+        assert_eq!(result.residual, 10);
+        assert!(result.resting);
+        assert_eq!(result.trades.len(), 2);
+        assert_eq!(book.level_orders(Side::Buy, 101), Some(vec![(4, 10)]));
+
+        // a FaK's residual doesn't rest
+        let fak = Order::new(OrderType::FillAndKill, 5, Side::Sell, 101, 15).to_order_ref();
+        let result = book.add_order_with_result(fak)?;
+        assert_eq!(result.aggressor_filled, 10);
+        assert_eq!(result.aggressor_vwap, Some(101.0));
+        assert_eq!(result.residual, 5);
+        assert!(!result.resting);
+        Ok(())
+    }
+
+    #[test]
+    fn test_good_after_time() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        let scheduled = |order_id, side, price, quantity, activation_time| {
+            Order::new(
+                OrderType::GoodAfterTime(activation_time),
+                order_id,
+                side,
+                price,
+                quantity,
+            )
         };
+        book.add_order(gtc(1, Side::Sell, 100, 10))?;
+        assert!(book.submit(scheduled(2, Side::Buy, 100, 4, 50))?.is_none());
+        book.submit(scheduled(3, Side::Buy, 101, 4, 60))?;
 
-        let order = book_side
-            .get(&order_entry.price)
-            .ok_or(OrderNotFound(*order_id))?
-            .get(&order_id)
-            .ok_or(OrderNotFound(*order_id))?;
+        // inert before its time: no trade, not displayed
+        assert!(book.activate_scheduled(49)?.is_none());
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.open_order_ids(), vec![1]);
+        assert!(matches!(
+            book.add_order(gtc(2, Side::Buy, 90, 1)),
+            Err(OrderBookError::OrderAlreadyExists(2))
+        ));
 
-        Ok(order)
+        let trades = book.activate_scheduled(50)?.unwrap_or_default();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].get_bid_trade().order_id, 2);
+        assert_eq!(book.level_orders(Side::Sell, 100), Some(vec![(1, 6)]));
+
+        // cancelled before activation, and arriving once its time has passed
+        book.cancel_order(3)?;
+        assert!(book.activate_scheduled(60)?.is_none());
+        let trades = book.submit(scheduled(4, Side::Buy, 100, 6, 55))?;
+        assert_eq!(trades.map(|trades| trades.len()), Some(1));
+        assert!(book.open_order_ids().is_empty());
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_rejected_activation_stays_scheduled() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.submit(Order::new(
+            OrderType::GoodAfterTime(10),
+            1,
+            Side::Buy,
+            100,
+            4,
+        ))?;
+        book.submit(Order::new(
+            OrderType::GoodAfterTime(10),
+            2,
+            Side::Buy,
+            99,
+            8,
+        ))?;
+
+        book.set_max_order_quantity(Some(5));
+        assert!(matches!(
+            book.activate_scheduled(10),
+            Err(QuantityTooLarge { order_id: 2, .. })
+        ));
+        assert!(book.open_order_ids().is_empty());
+        assert!(book.get_order_ref(&2).is_ok());
+
+        book.set_max_order_quantity(None);
+        book.activate_scheduled(10)?;
+        let mut open_ids = book.open_order_ids();
+        open_ids.sort();
+        assert_eq!(open_ids, vec![1, 2]);
+        Ok(())
+    }
 
     #[test]
-    fn test_add_order() -> BookResult<()> {
-        todo!()
+    fn test_price_at_depth() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Sell, 101, 10))?;
+        book.add_order(gtc(2, Side::Sell, 102, 20))?;
+        book.add_order(gtc(3, Side::Buy, 99, 15))?;
+        book.add_order(gtc(4, Side::Buy, 97, 5))?;
+
+        assert_eq!(book.price_at_depth(Side::Sell, 10), Some(101));
+        // spans both ask levels
+        assert_eq!(book.price_at_depth(Side::Sell, 25), Some(102));
+        assert_eq!(book.price_at_depth(Side::Sell, 31), None);
+        assert_eq!(book.price_at_depth(Side::Buy, 16), Some(97));
+        assert_eq!(book.price_at_depth(Side::Buy, 20), Some(97));
+
+        // the aggressor's sweep agrees
+        let sweep = book.simulate_sweep(Side::Buy, 25);
+        assert_eq!(sweep.get_fills().last().map(|(price, _)| *price), Some(102));
+        Ok(())
     }
 
     #[test]
-    fn test_add_duplicate_order_id() -> BookResult<()> {
-        todo!()
+    fn test_cancel_replace_rejected_replacement_keeps_old_order() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.set_strict_order_ids(true);
+        book.add_order(gtc(1, Side::Buy, 100, 10))?;
+
+        // the replacement takes over the old order's id
+        book.cancel_replace(
+            1,
+            Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 101, 10),
+        )?;
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 10)]);
+
+        // the replaced id is used up once another order takes its place
+        book.cancel_replace(
+            1,
+            Order::new(OrderType::GoodTillCancel, 2, Side::Buy, 101, 10),
+        )?;
+        let result = book.cancel_replace(
+            2,
+            Order::new(OrderType::GoodTillCancel, 1, Side::Buy, 102, 10),
+        );
+        assert!(matches!(result, Err(OrderIdRecentlyUsed(1))));
+        assert_eq!(book.open_order_ids(), vec![2]);
+
+        // a FaK with nothing to trade against
+        book.set_reject_on_empty_opposite(true);
+        let result =
+            book.cancel_replace(2, Order::new(OrderType::FillAndKill, 3, Side::Buy, 102, 10));
+        assert!(matches!(result, Err(NoLiquidity(3))));
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(101, 10)]);
+
+        // nor when the only liquidity is the order being replaced
+        book.add_order(gtc(4, Side::Sell, 105, 10))?;
+        let result =
+            book.cancel_replace(4, Order::new(OrderType::FillAndKill, 5, Side::Buy, 105, 10));
+        assert!(matches!(result, Err(NoLiquidity(5))));
+        assert_eq!(levels(book.get_order_infos().get_asks()), vec![(105, 10)]);
+        Ok(())
     }
 
     #[test]
-    fn test_cancel_order_non_existent_id() -> BookResult<()> {
-        todo!()
+    fn test_cancel_replace_held_orders() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.submit(Order::new(
+            OrderType::GoodAfterTime(50),
+            9,
+            Side::Buy,
+            100,
+            10,
+        ))?;
+        book.submit(Order::new(OrderType::PegMid, 11, Side::Sell, 0, 10))?;
+
+        // neither is in a level, but both can be replaced like they can be cancelled
+        book.cancel_replace(
+            9,
+            Order::new(OrderType::GoodTillCancel, 10, Side::Buy, 99, 10),
+        )?;
+        book.cancel_replace(
+            11,
+            Order::new(OrderType::GoodTillCancel, 12, Side::Sell, 101, 10),
+        )?;
+
+        let mut open_ids = book.open_order_ids();
+        open_ids.sort();
+        assert_eq!(open_ids, vec![10, 12]);
+        assert!(matches!(book.cancel_order(9), Err(OrderNotFound(9))));
+        assert!(matches!(book.cancel_order(11), Err(OrderNotFound(11))));
+        Ok(())
     }
 
     #[test]
-    fn test_cancel_order() -> BookResult<()> {
-        todo!()
+    fn test_pro_rata_allocation_uses_book_rounding() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        assert_eq!(book.get_rounding(), &Rounding::FloorThenLargestRemainder);
+        book.add_order(gtc(1, Side::Sell, 101, 30))?;
+        book.add_order(gtc(2, Side::Sell, 101, 30))?;
+        book.add_order(gtc(3, Side::Sell, 101, 30))?;
+        book.add_order(gtc(4, Side::Sell, 101, 11))?;
+
+        // exact shares are 29.70, 29.70, 29.70 and 10.89
+        assert_eq!(
+            book.pro_rata_allocation(Side::Sell, 101, 100),
+            vec![(1, 30), (2, 30), (3, 29), (4, 11)]
+        );
+        book.set_rounding(Rounding::FloorThenTimePriority);
+        assert_eq!(
+            book.pro_rata_allocation(Side::Sell, 101, 100),
+            vec![(1, 30), (2, 30), (3, 30), (4, 10)]
+        );
+        assert!(book.pro_rata_allocation(Side::Sell, 102, 100).is_empty());
+        Ok(())
     }
 
     #[test]
-    fn test_modify_order() -> BookResult<()> {
-        todo!()
+    fn test_rejected_peg_is_parked() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 98, 3))?;
+        book.add_order(gtc(2, Side::Sell, 104, 3))?;
+        book.submit(Order::new(OrderType::PegMid, 10, Side::Buy, 0, 5))?;
+        assert_eq!(book.level_orders(Side::Buy, 101), Some(vec![(10, 5)]));
+
+        // the peg no longer fits the limit when the midpoint moves
+        book.set_max_order_quantity(Some(4));
+        book.add_order(gtc(3, Side::Sell, 102, 3))?;
+        assert_eq!(levels(book.get_order_infos().get_bids()), vec![(98, 3)]);
+        assert!(book.get_order_ref(&10).is_ok());
+
+        book.set_max_order_quantity(None);
+        book.add_order(gtc(4, Side::Buy, 97, 3))?;
+        assert_eq!(book.level_orders(Side::Buy, 100), Some(vec![(10, 5)]));
+        Ok(())
     }
 
     #[test]
-    fn test_modify_non_existent_order() -> BookResult<()> {
-        todo!()
+    fn test_fak_remainder_cancelled_when_pass_yields() -> BookResult<()> {
+        for side in [Side::Buy, Side::Sell] {
+            let mut book = OrderBook::new("QQQ");
+            book.set_max_fills_per_match(Some(1));
+            book.add_order(gtc(1, side.opposite(), 100, 4))?;
+            book.add_order(gtc(2, side.opposite(), 100, 4))?;
+
+            let trades = book.submit(Order::new(OrderType::FillAndKill, 3, side, 100, 10))?;
+            assert_eq!(trades.map(|trades| trades.len()), Some(1));
+            assert_eq!(book.open_order_ids(), vec![2]);
+            assert_eq!(book.continue_matching()?, None);
+        }
+        Ok(())
     }
 
     #[test]
-    fn test_order_infos_book_empty_state() -> BookResult<()> {
-        todo!()
+    fn test_peg_trades_returned_by_triggering_add() -> BookResult<()> {
+        let mut book = OrderBook::new("QQQ");
+        book.add_order(gtc(1, Side::Buy, 98, 10))?;
+        book.add_order(gtc(2, Side::Sell, 103, 10))?;
+        book.submit(Order::new(OrderType::PegMid, 10, Side::Buy, 0, 5))?;
+        book.submit(Order::new(OrderType::PegMid, 11, Side::Sell, 0, 5))?;
+        assert_eq!(book.level_orders(Side::Buy, 100), Some(vec![(10, 5)]));
+        assert_eq!(book.level_orders(Side::Sell, 101), Some(vec![(11, 5)]));
+
+        // the new bid doesn't trade, but moves both pegs onto a midpoint of 101
+        let trades = book.add_order(gtc(3, Side::Buy, 99, 10))?.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].get_bid_trade().order_id, 10);
+        assert_eq!(trades[0].get_ask_trade().order_id, 11);
+        assert_eq!(*trades[0].get_price(), 101);
+        Ok(())
     }
 
     #[test]
-    fn test_order_infos_book_non_empty_state() -> BookResult<()> {
-        todo!()
+    fn test_fak_cancelled_when_crossed_pass_yields_before_it() -> BookResult<()> {
+        for side in [Side::Buy, Side::Sell] {
+            let mut book = OrderBook::new("QQQ");
+            book.set_max_fills_per_match(Some(1));
+            book.add_order(gtc(1, side.opposite(), 100, 4))?;
+            book.add_order(gtc(2, side.opposite(), 100, 4))?;
+            book.add_order(gtc(3, side, 100, 10))?;
+            let mut open_ids = book.open_order_ids();
+            open_ids.sort();
+            assert_eq!(open_ids, vec![2, 3]);
+
+            // the pass yields on orders 3 and 2, so the FaK never gets to trade
+            let trades = book.submit(Order::new(OrderType::FillAndKill, 4, side, 100, 5))?;
+            assert_eq!(trades.map(|trades| trades.len()), Some(1));
+            assert_eq!(book.open_order_ids(), vec![3]);
+            assert!(matches!(book.cancel_order(4), Err(OrderNotFound(4))));
+            assert_eq!(book.continue_matching()?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_held_fak_cancelled_when_liquidity_gone_by_resume() -> BookResult<()> {
+        for phase in [Phase::Auction, Phase::Halted] {
+            let mut book = OrderBook::new("QQQ");
+            book.set_phase(phase)?;
+            book.add_order(gtc(1, Side::Sell, 100, 5))?;
+            // crosses as it arrives, so it rests waiting for the uncross
+            book.submit(Order::new(OrderType::FillAndKill, 2, Side::Buy, 100, 5))?;
+            assert_eq!(book.level_orders(Side::Buy, 100), Some(vec![(2, 5)]));
+
+            book.cancel_order(1)?;
+            assert_eq!(book.resume()?, None);
+            assert!(book.open_order_ids().is_empty(), "{phase:?}");
+            assert!(matches!(book.cancel_order(2), Err(OrderNotFound(2))));
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::order::OwnerId;
+
+/// Source of the current time for a [`RateLimiter`], so it can run off simulated time.
+pub trait Clock: Send {
+    /// Time elapsed since some fixed starting point.
+    fn now(&self) -> Duration;
+}
+
+/// Wall clock time, measured from the clock's creation.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time, so one can be handed to a
+/// [`RateLimiter`] while another advances it.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Token bucket per owner, allowing bursts of up to `messages` and refilling at `messages`
+/// per `interval`.
+pub struct RateLimiter {
+    messages: u32,
+    interval: Duration,
+    clock: Box<dyn Clock>,
+    // owner -> (tokens left, time they were counted at)
+    buckets: HashMap<OwnerId, (f64, Duration)>,
+}
+
+impl RateLimiter {
+    pub fn new(messages: u32, interval: Duration, clock: Box<dyn Clock>) -> Self {
+        Self {
+            messages,
+            interval,
+            clock,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes a token from `owner`'s bucket. Returns false, taking nothing, if it's empty.
+    pub fn try_acquire(&mut self, owner: OwnerId) -> bool {
+        let now = self.clock.now();
+        let capacity = self.messages as f64;
+        let (tokens, counted_at) = self.buckets.entry(owner).or_insert((capacity, now));
+
+        if !self.interval.is_zero() {
+            let refill =
+                now.saturating_sub(*counted_at).as_secs_f64() / self.interval.as_secs_f64();
+            *tokens = (*tokens + refill * capacity).min(capacity);
+        } else {
+            *tokens = capacity;
+        }
+        *counted_at = now;
+
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_refills() {
+        let clock = ManualClock::new();
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(1), Box::new(clock.clone()));
+
+        assert!(limiter.try_acquire(7));
+        assert!(limiter.try_acquire(7));
+        assert!(!limiter.try_acquire(7));
+        // buckets are per owner
+        assert!(limiter.try_acquire(8));
+
+        // half an interval refills one of two tokens
+        clock.advance(Duration::from_millis(500));
+        assert!(limiter.try_acquire(7));
+        assert!(!limiter.try_acquire(7));
+
+        // never more than a full bucket
+        clock.advance(Duration::from_secs(10));
+        assert!(limiter.try_acquire(7));
+        assert!(limiter.try_acquire(7));
+        assert!(!limiter.try_acquire(7));
+    }
+}
@@ -1,29 +1,228 @@
-use crate::order::{OrderId, Price, Quantity};
+use std::sync::Arc;
+
+use crate::{
+    error::{WireError, WireResult},
+    order::{OrderId, OwnerId, Price, Quantity},
+    wire::{WireReader, WireWriter},
+};
 
 /// Represents a successful trade.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Trade {
     // matched bid and ask
     bid_trade: TradeInfo,
     ask_trade: TradeInfo,
+    // price the trade executed at, by default the resting order's price
+    price: Price,
 }
 
 /// Information about completed trade.
-#[derive(Clone, Debug)]
+/// `price` is the order's own limit price, see [`Trade::get_price`] for the execution price.
+#[derive(Clone, Debug, PartialEq)]
 pub struct TradeInfo {
     pub order_id: OrderId,
     pub price: Price,
     pub quantity: Quantity,
+    pub owner: OwnerId,
+    // tag of the order, if it was given one
+    pub tag: Option<Arc<str>>,
 }
 
 impl Trade {
-    pub fn new(bid_trade: TradeInfo, ask_trade: TradeInfo) -> Self {
+    pub fn new(bid_trade: TradeInfo, ask_trade: TradeInfo, price: Price) -> Self {
         Self {
-            bid_trade: bid_trade,
-            ask_trade: ask_trade,
+            bid_trade,
+            ask_trade,
+            price,
+        }
+    }
+
+    pub fn get_bid_trade(&self) -> &TradeInfo {
+        &self.bid_trade
+    }
+    pub fn get_ask_trade(&self) -> &TradeInfo {
+        &self.ask_trade
+    }
+    pub fn get_price(&self) -> &Price {
+        &self.price
+    }
+    pub fn get_quantity(&self) -> &Quantity {
+        &self.bid_trade.quantity
+    }
+
+    /// Value of the trade at its execution price.
+    /// Computed in i128, as notionals summed over several trades quickly outgrow i64.
+    pub fn get_notional(&self) -> i128 {
+        self.price as i128 * *self.get_quantity() as i128
+    }
+
+    /// Borrows the trade as a [`TradeView`].
+    pub fn view(&self) -> TradeView<'_> {
+        TradeView {
+            bid_trade: &self.bid_trade,
+            ask_trade: &self.ask_trade,
+            price: self.price,
         }
     }
+
+    /// Encodes the trade in the binary wire format, all fields little-endian:
+    ///
+    /// | offset | size | field |
+    /// |--------|------|-------|
+    /// | 0  | 1  | version |
+    /// | 1  | 24 | bid side [`TradeInfo`] |
+    /// | 25 | 24 | ask side [`TradeInfo`] |
+    /// | 49 | 4  | execution price |
+    ///
+    /// Each [`TradeInfo`] is its order id (8 bytes), price (4), quantity (4) and owner (8).
+    /// Tags aren't encoded, decoded trades come back untagged.
+    pub fn to_bytes(&self) -> [u8; TRADE_WIRE_LEN] {
+        let mut writer = WireWriter::new();
+        for info in [&self.bid_trade, &self.ask_trade] {
+            writer
+                .put(&info.order_id.to_le_bytes())
+                .put(&info.price.to_le_bytes())
+                .put(&info.quantity.to_le_bytes())
+                .put(&info.owner.to_le_bytes());
+        }
+        writer.put(&self.price.to_le_bytes()).finish()
+    }
+
+    /// Decodes a trade written by [`to_bytes`](Self::to_bytes). Bytes past the trade are ignored.
+    ///
+    /// # Errors:
+    /// - Returns [`Truncated`](crate::error::WireError)
+    /// - Returns [`UnsupportedVersion`](crate::error::WireError)
+    /// - Returns [`TradeQuantityMismatch`](crate::error::WireError)
+    pub fn from_bytes(bytes: &[u8]) -> WireResult<Self> {
+        let mut reader = WireReader::new(bytes, TRADE_WIRE_LEN)?;
+        let mut read_info = || TradeInfo {
+            order_id: reader.i64(),
+            price: reader.i32(),
+            quantity: reader.u32(),
+            owner: reader.u64(),
+            tag: None,
+        };
+        let bid_trade = read_info();
+        let ask_trade = read_info();
+        // both sides of a trade fill the same quantity
+        if bid_trade.quantity != ask_trade.quantity {
+            return Err(WireError::TradeQuantityMismatch {
+                bid: bid_trade.quantity,
+                ask: ask_trade.quantity,
+            });
+        }
+        Ok(Self::new(bid_trade, ask_trade, reader.i32()))
+    }
+}
+
+/// Non-owning view of a [`Trade`], handed to trade listeners so they can read a fill without
+/// cloning it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TradeView<'a> {
+    bid_trade: &'a TradeInfo,
+    ask_trade: &'a TradeInfo,
+    price: Price,
+}
+
+impl TradeView<'_> {
+    pub fn get_bid_trade(&self) -> &TradeInfo {
+        self.bid_trade
+    }
+    pub fn get_ask_trade(&self) -> &TradeInfo {
+        self.ask_trade
+    }
+    pub fn get_price(&self) -> &Price {
+        &self.price
+    }
+    pub fn get_quantity(&self) -> &Quantity {
+        &self.bid_trade.quantity
+    }
+
+    /// See [`Trade::get_notional`].
+    pub fn get_notional(&self) -> i128 {
+        self.price as i128 * *self.get_quantity() as i128
+    }
+
+    /// Clones the viewed trade into an owned [`Trade`].
+    pub fn to_trade(&self) -> Trade {
+        Trade::new(self.bid_trade.clone(), self.ask_trade.clone(), self.price)
+    }
 }
 
+/// Length of a [`Trade`] in the binary wire format.
+pub const TRADE_WIRE_LEN: usize = 53;
+
 /// Collection of Trades.
 pub type Trades = Vec<Trade>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade() -> Trade {
+        Trade::new(
+            TradeInfo {
+                order_id: 1,
+                price: 101,
+                quantity: 30,
+                owner: 7,
+                tag: None,
+            },
+            TradeInfo {
+                order_id: -2,
+                price: -100,
+                quantity: 30,
+                owner: u64::MAX,
+                tag: None,
+            },
+            -100,
+        )
+    }
+
+    #[test]
+    fn test_trade_wire_round_trip() {
+        let bytes = trade().to_bytes();
+        assert_eq!(Trade::from_bytes(&bytes), Ok(trade()));
+
+        // trailing bytes belong to whatever follows the trade
+        let mut buffer = bytes.to_vec();
+        buffer.extend_from_slice(&[0xff; 4]);
+        assert_eq!(Trade::from_bytes(&buffer), Ok(trade()));
+    }
+
+    #[test]
+    fn test_trade_wire_truncated() {
+        let bytes = trade().to_bytes();
+        for len in [0, 1, TRADE_WIRE_LEN - 1] {
+            assert_eq!(
+                Trade::from_bytes(&bytes[..len]),
+                Err(WireError::Truncated {
+                    expected: TRADE_WIRE_LEN,
+                    actual: len
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_trade_wire_quantity_mismatch() {
+        let mut bytes = trade().to_bytes();
+        bytes[37..41].copy_from_slice(&999u32.to_le_bytes());
+        assert_eq!(
+            Trade::from_bytes(&bytes),
+            Err(WireError::TradeQuantityMismatch { bid: 30, ask: 999 })
+        );
+    }
+
+    #[test]
+    fn test_trade_view() {
+        let trade = trade();
+        let view = trade.view();
+        assert_eq!(view.get_bid_trade(), trade.get_bid_trade());
+        assert_eq!(view.get_ask_trade(), trade.get_ask_trade());
+        assert_eq!(view.get_price(), trade.get_price());
+        assert_eq!(view.get_notional(), trade.get_notional());
+        assert_eq!(view.to_trade(), trade);
+    }
+}
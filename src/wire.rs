@@ -0,0 +1,85 @@
+//! Fixed layout little-endian encoding of orders and trades.
+//! Every message starts with a [`WIRE_VERSION`] byte, see [`Order::to_bytes`](crate::order::Order::to_bytes)
+//! and [`Trade::to_bytes`](crate::trade::Trade::to_bytes) for the field layouts.
+
+use crate::error::{WireError, WireResult};
+
+/// Version byte written at the start of every encoded message.
+pub const WIRE_VERSION: u8 = 3;
+
+/// Writes fields back to back into a fixed size buffer.
+pub(crate) struct WireWriter<const N: usize> {
+    bytes: [u8; N],
+    position: usize,
+}
+
+impl<const N: usize> WireWriter<N> {
+    pub(crate) fn new() -> Self {
+        let mut writer = Self {
+            bytes: [0; N],
+            position: 0,
+        };
+        writer.put(&[WIRE_VERSION]);
+        writer
+    }
+
+    pub(crate) fn put(&mut self, field: &[u8]) -> &mut Self {
+        self.bytes[self.position..self.position + field.len()].copy_from_slice(field);
+        self.position += field.len();
+        self
+    }
+
+    pub(crate) fn finish(&self) -> [u8; N] {
+        debug_assert_eq!(self.position, N);
+        self.bytes
+    }
+}
+
+/// Reads fields back to back from a buffer whose length and version were already checked.
+pub(crate) struct WireReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> WireReader<'a> {
+    /// Checks the buffer holds a whole message of `len` bytes in the current version.
+    ///
+    /// # Errors:
+    /// - Returns [`Truncated`](crate::error::WireError)
+    /// - Returns [`UnsupportedVersion`](crate::error::WireError)
+    pub(crate) fn new(bytes: &'a [u8], len: usize) -> WireResult<Self> {
+        if bytes.len() < len {
+            return Err(WireError::Truncated {
+                expected: len,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[0] != WIRE_VERSION {
+            return Err(WireError::UnsupportedVersion(bytes[0]));
+        }
+        Ok(Self {
+            bytes: &bytes[1..len],
+        })
+    }
+
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (field, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        field.try_into().unwrap()
+    }
+
+    pub(crate) fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+    pub(crate) fn i32(&mut self) -> i32 {
+        i32::from_le_bytes(self.take())
+    }
+    pub(crate) fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+    pub(crate) fn i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.take())
+    }
+    pub(crate) fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+}
@@ -0,0 +1,62 @@
+//! Timing of the fast path for adds that can't cross. Ignored by default, run it with
+//! `cargo test --release --test add_bench -- --ignored --nocapture`.
+
+use std::time::{Duration, Instant};
+
+use orderbook::{error::BookResult, order::*, orderbook::OrderBook};
+
+const ADDS: OrderId = 100_000;
+
+/// Time taken to rest `ADDS` bids below a lone ask, running a match pass after each add if
+/// `match_each`.
+fn resting_adds(match_each: bool) -> BookResult<Duration> {
+    let mut book = OrderBook::new("QQQ");
+    book.submit(Order::new(
+        OrderType::GoodTillCancel,
+        0,
+        Side::Sell,
+        20_000,
+        1,
+    ))?;
+
+    let start = Instant::now();
+    for order_id in 1..=ADDS {
+        // spread over 100 levels, all below the ask
+        let price = 10_000 + (order_id % 100) as Price;
+        book.submit(Order::new(
+            OrderType::GoodTillCancel,
+            order_id,
+            Side::Buy,
+            price,
+            1,
+        ))?;
+        if match_each {
+            book.uncross()?;
+        }
+    }
+    Ok(start.elapsed())
+}
+
+/// Fastest of a few runs, to keep scheduling noise out of the comparison.
+fn best_of(runs: usize, match_each: bool) -> BookResult<Duration> {
+    let mut best = Duration::MAX;
+    for _ in 0..runs {
+        best = best.min(resting_adds(match_each)?);
+    }
+    Ok(best)
+}
+
+#[test]
+#[ignore]
+fn bench_non_crossing_adds() -> BookResult<()> {
+    let fast_path = best_of(5, false)?;
+    // what every add paid before the fast path: a match pass after placing the order
+    let with_match_pass = best_of(5, true)?;
+
+    println!(
+        "{ADDS} non-crossing adds: {fast_path:?} with the fast path, \
+         {with_match_pass:?} with a match pass each"
+    );
+    assert!(fast_path < with_match_pass);
+    Ok(())
+}
@@ -1,7 +1,5 @@
-use std::{cell::RefCell, rc::Rc};
-
 use ::orderbook::orderbook::OrderBook;
-use orderbook::{error::BookResult, order::*, trade::*};
+use orderbook::{error::BookResult, order::*};
 
 // integration tests here
 
@@ -18,7 +16,7 @@ fn match_two_good_till_cancels() -> BookResult<()> {
         Side::Buy,
         bid_price,
         100 as Quantity,
-    ).to_order_ref();
+    );
 
     let ask = Order::new(
         OrderType::GoodTillCancel,
@@ -26,10 +24,10 @@ fn match_two_good_till_cancels() -> BookResult<()> {
         Side::Sell,
         ask_price,
         100 as Quantity,
-    ).to_order_ref();
+    );
 
-    book.add_order(bid)?;
-    let trade = book.add_order(ask)?;
+    book.submit(bid)?;
+    let trade = book.submit(ask)?;
     println!("{:?}", trade);
     assert!(trade.is_none());
     Ok(())
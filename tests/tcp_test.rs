@@ -0,0 +1,60 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use orderbook::{interop::tcp, orderbook::OrderBook};
+
+/// Sends one command and reads its response up to the closing `OK` or `ERR` line.
+fn request(
+    stream: &mut TcpStream,
+    reader: &mut BufReader<TcpStream>,
+    command: &str,
+) -> Vec<String> {
+    writeln!(stream, "{command}").unwrap();
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end().to_string();
+        let done = line == "OK" || line.starts_with("ERR");
+        lines.push(line);
+        if done {
+            return lines;
+        }
+    }
+}
+
+#[test]
+fn tcp_server_reports_trades_and_ladder() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || tcp::serve(listener, OrderBook::new("QQQ")));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    assert_eq!(
+        request(&mut stream, &mut reader, "ADD 1 B 10 100 GTC"),
+        ["OK"]
+    );
+    assert_eq!(
+        request(&mut stream, &mut reader, "ADD 2 S 4 100 GTC"),
+        ["TRADE 1 2 4 100", "OK"]
+    );
+    assert_eq!(
+        request(&mut stream, &mut reader, "ADD 3 S 5 102 GTC"),
+        ["OK"]
+    );
+    assert_eq!(
+        request(&mut stream, &mut reader, "BOOK"),
+        ["ASK 102 5", "BID 100 6", "OK"]
+    );
+    assert_eq!(request(&mut stream, &mut reader, "CANCEL 3"), ["OK"]);
+    assert!(request(&mut stream, &mut reader, "CANCEL 3")[0].starts_with("ERR"));
+    assert_eq!(
+        request(&mut stream, &mut reader, "ADD 4 X 5 102 GTC"),
+        ["ERR invalid side X"]
+    );
+}